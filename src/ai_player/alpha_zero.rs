@@ -0,0 +1,241 @@
+//! AlphaZero style play: PUCT tree search guided by a `Network`.
+//!
+//! The network plugs into the `SearchTree` through its usual extension points. As a `Prior`,
+//! its policy head decides which moves are expanded and explored first, and as a
+//! `PlayoutPolicy` it replaces random playouts by the expected result from its value head.
+//! Self-play records the visit distribution at the root and the final result for every
+//! position, which are the training targets of the two heads.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::network::{Network, Sample, DRAW, LOSS, WIN};
+use super::{think_with_tree, Expectation, EXACT_SEARCH_LINES, EXACT_SEARCH_PLIES, PlayoutPolicy, PlayoutRng, Prior, Puct, SearchConfig, SearchTree};
+use crate::{Analysis, ConnectFour, Game, Player, PlayerTrait, State, Thought};
+
+/// The default search configuration of the `NetworkPlayer`. The exploration weight is
+/// `c_puct`, the prior weight is not used by PUCT.
+pub const NETWORK_SEARCH_CONFIG: SearchConfig = SearchConfig {
+    simulations: 800,
+    exploration: 1.5,
+    prior_weight: 0.,
+    exact_search_plies: EXACT_SEARCH_PLIES,
+    exact_search_lines: EXACT_SEARCH_LINES,
+};
+
+
+impl<S: State> Prior<ConnectFour<S>> for Network {
+    fn priors(&self, game: &ConnectFour<S>, moves: &[usize]) -> Vec<f64> {
+        let policy = self.predict(game).policy;
+        moves.iter().map(|&column| policy[column]).collect()
+    }
+}
+
+/// Searches that back up sampled results draw them from the value head.
+impl<S: State> PlayoutPolicy<ConnectFour<S>> for Network {
+    fn playout(&self, rng: &mut PlayoutRng, game: ConnectFour<S>) -> Option<Player> {
+        if game.has_ended() {
+            return game.winner();
+        }
+        let value = self.predict(&game).value;
+        let roll: f64 = rng.gen();
+        if roll < value[WIN] {
+            Some(game.current_player())
+        }
+        else if roll < value[WIN] + value[DRAW] {
+            None
+        }
+        else {
+            Some(game.other_player())
+        }
+    }
+
+    fn expected_result(&self, _rng: &mut PlayoutRng, game: ConnectFour<S>) -> Expectation {
+        if game.has_ended() {
+            return Expectation::of(game.winner(), game.current_player());
+        }
+        let value = self.predict(&game).value;
+        Expectation { player: game.current_player(), win: value[WIN], draw: value[DRAW] }
+    }
+}
+
+
+fn network_tree<S: State>(network: &Arc<Network>, game: &ConnectFour<S>, config: SearchConfig) -> SearchTree<ConnectFour<S>, Puct> {
+    let mut tree = SearchTree::with_policy(game, config, network.clone(), Puct);
+    tree.set_playout(network.clone());
+    tree
+}
+
+
+#[derive(Clone)]
+pub struct NetworkPlayer<S: State> {
+    network: Arc<Network>,
+    config: SearchConfig,
+    _state: PhantomData<S>,
+}
+
+impl<S: State> NetworkPlayer<S> {
+    /// A player for games on the board of `game`. Fails if the network was built for another
+    /// board size, as it can only evaluate positions of its own size.
+    pub fn new(network: Arc<Network>, game: &ConnectFour<S>) -> Result<NetworkPlayer<S>, String> {
+        Self::with_config(network, game, NETWORK_SEARCH_CONFIG)
+    }
+
+    pub fn with_config(network: Arc<Network>, game: &ConnectFour<S>, config: SearchConfig) -> Result<NetworkPlayer<S>, String> {
+        if network.size() != game.size() {
+            let (columns, rows) = network.size();
+            return Err(format!(
+                "the network was built for a {}x{} board, not {}x{}", columns, rows, game.size().0, game.size().1,
+            ));
+        }
+        Ok(NetworkPlayer {
+            network,
+            config,
+            _state: PhantomData,
+        })
+    }
+}
+
+impl<S: State> PlayerTrait for NetworkPlayer<S> {
+    type Game = ConnectFour<S>;
+
+    fn make_move(&self, game: &ConnectFour<S>) -> usize {
+        self.analyse(game).0
+    }
+
+    fn analyse(&self, game: &ConnectFour<S>) -> Analysis<usize> {
+        self.think(game, &|| false).0
+    }
+
+    fn think(&self, game: &ConnectFour<S>, stop: &dyn Fn() -> bool) -> Thought<usize> {
+        think_with_tree(game, stop, |game| network_tree(&self.network, game, self.config))
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SelfPlayConfig {
+    pub search: SearchConfig,
+    /// During the first `sampling_plies` plies, moves are chosen with probabilities
+    /// proportional to their visits so that the games are varied. Afterwards the most visited
+    /// move is played.
+    pub sampling_plies: usize,
+}
+
+impl Default for SelfPlayConfig {
+    fn default() -> SelfPlayConfig {
+        SelfPlayConfig {
+            search: SearchConfig { simulations: 200, ..NETWORK_SEARCH_CONFIG },
+            sampling_plies: 8,
+        }
+    }
+}
+
+
+/// Plays `game` to the end with `network` on both sides and returns one training sample per
+/// position.
+pub fn self_play<S: State>(
+    network: &Arc<Network>,
+    mut game: ConnectFour<S>,
+    config: &SelfPlayConfig,
+    rng: &mut impl Rng,
+) -> Vec<Sample> {
+    let columns = game.size().0;
+    let mut positions = vec![];
+    while !game.has_ended() {
+        let mut tree = network_tree(network, &game, config.search);
+        tree.search(config.search.simulations);
+        let statistics = tree.move_statistics();
+        let total_visits: u64 = statistics.iter().map(|statistics| statistics.visits).sum();
+
+        let mut policy = vec![0.; columns];
+        for statistics in &statistics {
+            policy[statistics.move_] = statistics.visits as f64 / total_visits.max(1) as f64;
+        }
+        let mut move_ = statistics[0].move_;
+        if positions.len() < config.sampling_plies && total_visits > 0 {
+            let mut remaining = rng.gen_range(0, total_visits);
+            for statistics in &statistics {
+                if remaining < statistics.visits {
+                    move_ = statistics.move_;
+                    break;
+                }
+                remaining -= statistics.visits;
+            }
+        }
+
+        positions.push((network.encode(&game), policy, game.current_player()));
+        game.play(move_).unwrap();
+    }
+
+    let winner = game.winner();
+    positions.into_iter()
+        .map(|(input, policy, player)| {
+            let mut value = [0.; 3];
+            value[match winner {
+                None => DRAW,
+                Some(winner) if winner == player => WIN,
+                Some(_) => LOSS,
+            }] = 1.;
+            Sample { input, policy, value }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_player::new_rng;
+    use crate::BitState;
+
+    #[test]
+    fn self_play_records_every_position() {
+        let network = Arc::new(Network::new(5, 4, &[16], &mut new_rng()));
+        let config = SelfPlayConfig {
+            search: SearchConfig { simulations: 20, ..NETWORK_SEARCH_CONFIG },
+            sampling_plies: 4,
+        };
+        let samples = self_play(&network, ConnectFour::<BitState>::new(5, 4).unwrap(), &config, &mut new_rng());
+        assert!(samples.len() >= 7 && samples.len() <= 20);
+        for sample in &samples {
+            assert!((sample.policy.iter().sum::<f64>() - 1.).abs() < 1e-9);
+        }
+        // The last player to move either won or drew, and the results alternate between the
+        // players.
+        let last = samples.last().unwrap().value;
+        assert!(last == [1., 0., 0.] || last == [0., 1., 0.]);
+        for pair in samples.windows(2) {
+            assert_eq!(pair[0].value, [pair[1].value[LOSS], pair[1].value[DRAW], pair[1].value[WIN]]);
+        }
+    }
+
+    #[test]
+    fn network_player_takes_immediate_wins() {
+        let mut game = ConnectFour::<BitState>::new(7, 6).unwrap();
+        for &move_ in &[0, 6, 0, 6, 0, 6] {
+            game.play(move_).unwrap();
+        }
+        let network = Arc::new(Network::new(7, 6, &[16], &mut new_rng()));
+        let config = SearchConfig { simulations: 300, ..NETWORK_SEARCH_CONFIG };
+        assert_eq!(NetworkPlayer::with_config(network.clone(), &game, config).unwrap().make_move(&game), 0);
+        assert!(NetworkPlayer::new(network, &ConnectFour::<BitState>::new(6, 5).unwrap()).is_err());
+    }
+
+    #[test]
+    fn the_tree_search_backs_up_the_expected_value() {
+        let game = ConnectFour::<BitState>::new(5, 4).unwrap();
+        let network = Arc::new(Network::new(5, 4, &[8], &mut new_rng()));
+        let mut tree = network_tree(&network, &game, SearchConfig { exact_search_plies: 0, exact_search_lines: 0, ..NETWORK_SEARCH_CONFIG });
+        tree.search(1);
+        let visited = tree.move_statistics()[0];
+        assert_eq!(visited.visits, 1);
+        let mut child = game.clone();
+        child.play(visited.move_).unwrap();
+        // A sampled result would be 0, 0.5 or 1.
+        let (wins, draws) = network.expected_result(&mut new_rng(), child).for_player(game.current_player());
+        assert!((visited.value - (wins + draws / 2.)).abs() < 1e-12, "{} {}", visited.value, wins + draws / 2.);
+    }
+}
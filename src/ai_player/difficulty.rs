@@ -0,0 +1,342 @@
+//! Weaker opponents for casual play.
+//!
+//! A `DifficultyPlayer` searches with a reduced playout budget and deliberately deviates from
+//! the best move now and then. Its mistakes are modelled after human ones: it only ever picks
+//! moves that the search considers reasonable alternatives, and never a move that allows the
+//! opponent to win immediately.
+//!
+//! `Difficulty::Perfect` makes no mistakes and solves every position with at most
+//! `PERFECT_SOLVE_PLIES` moves left with `minimax::solve`, and every position covered by an
+//! `Oracle` such as a `Tablebase`, so it plays perfectly where that is feasible. Earlier
+//! positions of larger boards are too expensive to solve and are searched like the other levels,
+//! as are positions whose solving is stopped.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::{minimax, new_rng, MoveStatistics, SearchConfig, SearchTree, SIMULATIONS};
+use crate::{tactics, Analysis, Game, Oracle, PlayerTrait, SearchInfo, Thought};
+
+/// Alternative moves are only considered if their value is at most this much worse than the
+/// value of the best move.
+const PLAUSIBILITY_MARGIN: f64 = 0.3;
+
+/// `Difficulty::Perfect` solves positions with at most this many moves left. Solving takes
+/// milliseconds at this depth on the standard board, but grows by an order of magnitude every
+/// other ply.
+pub const PERFECT_SOLVE_PLIES: usize = 18;
+
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+    /// The strongest setting: no deliberate mistakes, the largest playout budget, and
+    /// perfect play wherever the position can be solved.
+    Perfect,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 6] = [
+        Difficulty::Beginner,
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Expert,
+        Difficulty::Perfect,
+    ];
+
+    pub fn settings(self) -> DifficultySettings {
+        let (simulations, random_probability, second_best_probability, tactical_awareness, solve_plies) =
+            match self {
+                Difficulty::Beginner => (300, 0.4, 0.2, false, 0),
+                Difficulty::Easy => (1_500, 0.2, 0.2, false, 0),
+                Difficulty::Medium => (5_000, 0.1, 0.15, true, 0),
+                Difficulty::Hard => (20_000, 0.02, 0.08, true, 0),
+                Difficulty::Expert => (SIMULATIONS, 0., 0., true, 0),
+                Difficulty::Perfect => (4 * SIMULATIONS, 0., 0., true, PERFECT_SOLVE_PLIES),
+            };
+        DifficultySettings { simulations, random_probability, second_best_probability, tactical_awareness, solve_plies }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "beginner",
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+            Difficulty::Perfect => "perfect",
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Difficulty, String> {
+        Difficulty::ALL.iter()
+            .cloned()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = Difficulty::ALL.iter().map(|difficulty| difficulty.name()).collect();
+                format!("unknown difficulty `{}`, expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DifficultySettings {
+    /// Number of playouts per move.
+    pub simulations: usize,
+    /// Probability of playing a random plausible move instead of the best one.
+    pub random_probability: f64,
+    /// Probability of playing the second best move instead of the best one.
+    pub second_best_probability: f64,
+    /// Always take immediate wins and block the opponent’s immediate wins.
+    pub tactical_awareness: bool,
+    /// Positions with at most this many moves left (see `Game::remaining_moves`) are solved
+    /// and played perfectly, 0 disables solving.
+    pub solve_plies: usize,
+}
+
+
+#[derive(Clone)]
+pub struct DifficultyPlayer<G: Game> {
+    settings: DifficultySettings,
+    oracle: Option<Arc<dyn Oracle<G>>>,
+    _game: PhantomData<G>,
+}
+
+impl<G: Game> DifficultyPlayer<G> {
+    pub fn new(difficulty: Difficulty) -> DifficultyPlayer<G> {
+        Self::with_settings(difficulty.settings())
+    }
+
+    pub fn with_settings(settings: DifficultySettings) -> DifficultyPlayer<G> {
+        DifficultyPlayer {
+            settings,
+            oracle: None,
+            _game: PhantomData,
+        }
+    }
+
+    /// Positions covered by `oracle`, for example a `Tablebase`, are played perfectly, so this
+    /// is meant for `Difficulty::Perfect`.
+    pub fn with_oracle(self, oracle: Arc<dyn Oracle<G>>) -> DifficultyPlayer<G> {
+        DifficultyPlayer {
+            oracle: Some(oracle),
+            ..self
+        }
+    }
+
+    /// Chooses the move to play from the search results, which are sorted by visits. With
+    /// tactical awareness, the best move is the most visited one that does not let the
+    /// opponent win immediately, as behind `tactics::safety_net`.
+    fn choose_move(&self, game: &G, statistics: &[MoveStatistics<G::Move>], rng: &mut impl Rng) -> G::Move {
+        let losing_moves = tactics::losing_moves(game);
+        let best = statistics.iter()
+            .find(|statistics| !self.settings.tactical_awareness || !losing_moves.contains(&statistics.move_))
+            .or_else(|| statistics.first())
+            .expect("Could not find valid move");
+        let plausible_alternatives: Vec<_> = statistics.iter()
+            .filter(|alternative| {
+                alternative.move_ != best.move_
+                    && alternative.visits > 0
+                    && best.value - alternative.value <= PLAUSIBILITY_MARGIN
+                    && !losing_moves.contains(&alternative.move_)
+            })
+            .collect();
+
+        let roll: f64 = rng.gen();
+        if roll < self.settings.second_best_probability {
+            if let Some(second_best) = plausible_alternatives.first() {
+                return second_best.move_;
+            }
+        }
+        else if roll < self.settings.second_best_probability + self.settings.random_probability {
+            // More visited alternatives are more tempting, just as for human players.
+            let total_visits: u64 = plausible_alternatives.iter().map(|alternative| alternative.visits).sum();
+            if total_visits > 0 {
+                let mut remaining = rng.gen_range(0, total_visits);
+                for alternative in plausible_alternatives {
+                    if remaining < alternative.visits {
+                        return alternative.move_;
+                    }
+                    remaining -= alternative.visits;
+                }
+            }
+        }
+        best.move_
+    }
+}
+
+impl<G: Game> PlayerTrait for DifficultyPlayer<G> {
+    type Game = G;
+
+    fn make_move(&self, game: &G) -> G::Move {
+        self.analyse(game).0
+    }
+
+    fn analyse(&self, game: &G) -> Analysis<G::Move> {
+        self.think(game, &|| false).0
+    }
+
+    fn think(&self, game: &G, stop: &dyn Fn() -> bool) -> Thought<G::Move> {
+        if let Some(move_) = self.oracle.as_ref().and_then(|oracle| oracle.best_move(game)) {
+            return ((move_, vec![]), SearchInfo::from_statistics(move_, &[]));
+        }
+        if game.remaining_moves().is_some_and(|remaining| remaining <= self.settings.solve_plies) {
+            // A stopped solver leaves the move to the search.
+            if let Some(thought) = solve(game, stop) {
+                return thought;
+            }
+        }
+        if self.settings.tactical_awareness {
+            if let Some(move_) = tactics::forced_move(game) {
+                return ((move_, vec![]), SearchInfo::from_statistics(move_, &[]));
+            }
+        }
+
+        let config = SearchConfig { simulations: self.settings.simulations, ..SearchConfig::default() };
+        let mut tree = SearchTree::with_config(game, config);
+        tree.search_until(config.simulations, stop);
+        let statistics = tree.move_statistics();
+        let move_ = self.choose_move(game, &statistics, &mut new_rng());
+        // The search's principal variation only applies if the player didn't deviate from it.
+        let info = Some(tree.search_info())
+            .filter(|info| info.principal_variation.first() == Some(&move_))
+            .unwrap_or_else(|| SearchInfo::from_statistics(move_, &statistics));
+        ((move_, statistics), info)
+    }
+}
+
+
+/// Plays the move with the best result with perfect play, preferring wins over draws over
+/// losses. The statistics report the exact results without visits. Returns `None` if `stop`
+/// returns `true` before every move is solved.
+fn solve<G: Game>(game: &G, stop: &dyn Fn() -> bool) -> Option<Thought<G::Move>> {
+    let me = game.current_player();
+    let mut statistics = game.valid_moves()
+        .into_iter()
+        .map(|move_| {
+            let mut child = game.clone();
+            child.play(move_).unwrap_or_else(|err| panic!("tried to play invalid move: {:?}", err));
+            let value = match minimax::solve_until(&child, stop)? {
+                Some(winner) if winner == me => 1.,
+                Some(_) => 0.,
+                None => 0.5,
+            };
+            Some(MoveStatistics { move_, visits: 0, value })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    // The sort is stable, so equally good moves keep the order of `valid_moves`.
+    statistics.sort_by(|a, b| b.value.total_cmp(&a.value));
+    let best = *statistics.first().expect("Could not find valid move");
+    let info = SearchInfo {
+        depth: game.remaining_moves().unwrap_or(1),
+        visits: 0,
+        value: Some(best.value),
+        principal_variation: vec![best.move_],
+        report: None,
+    };
+    Some(((best.move_, statistics), info))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tablebase::Value;
+    use crate::{BitState, ConnectFour, Tablebase};
+
+    #[test]
+    fn difficulties_can_be_parsed() {
+        for &difficulty in Difficulty::ALL.iter() {
+            assert_eq!(difficulty.to_string().parse(), Ok(difficulty));
+        }
+        assert_eq!("Hard".parse(), Ok(Difficulty::Hard));
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn mistakes_are_plausible() {
+        let mut game = ConnectFour::<BitState>::new(7, 6).unwrap();
+        for &move_ in &[1, 1, 2, 2, 6, 3, 6, 3] {
+            game.play(move_).unwrap();
+        }
+        let player = DifficultyPlayer::with_settings(DifficultySettings {
+            simulations: 0,
+            random_probability: 1.,
+            second_best_probability: 0.,
+            tactical_awareness: false,
+            solve_plies: 0,
+        });
+        let statistics: Vec<_> = [5, 0, 4, 6]
+            .iter()
+            .map(|&move_| MoveStatistics { move_, visits: 10, value: 0.5 })
+            .chain(Some(MoveStatistics { move_: 1, visits: 10, value: 0. }))
+            .collect();
+        for _ in 0..100 {
+            // 0 and 4 lose immediately and 1 is much worse than the best move.
+            let move_ = player.choose_move(&game, &statistics, &mut new_rng());
+            assert!(move_ == 5 || move_ == 6, "unexpected move {}", move_);
+        }
+    }
+
+    #[test]
+    fn aware_players_do_not_play_into_immediate_losses() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[1, 1, 2, 2, 6, 3, 6, 3]);
+        let settings = DifficultySettings { random_probability: 0., second_best_probability: 0., ..Difficulty::Expert.settings() };
+        let statistics = |moves: &[usize]| -> Vec<_> {
+            moves.iter().map(|&move_| MoveStatistics { move_, visits: 10, value: 0.5 }).collect()
+        };
+        // The search ranks a move first that lets the opponent complete the second row.
+        for ranking in [[0, 4, 5, 6, 1], [4, 0, 6, 5, 1]] {
+            let move_ = DifficultyPlayer::with_settings(settings).choose_move(&game, &statistics(&ranking), &mut new_rng());
+            assert_eq!(move_, ranking[2]);
+        }
+        // Players without tactical awareness still blunder.
+        let careless = DifficultySettings { tactical_awareness: false, ..settings };
+        assert_eq!(DifficultyPlayer::with_settings(careless).choose_move(&game, &statistics(&[0, 5]), &mut new_rng()), 0);
+    }
+
+    #[test]
+    fn perfect_play_agrees_with_the_tablebase() {
+        let tablebase = Tablebase::build(4, 4);
+        let player = DifficultyPlayer::new(Difficulty::Perfect);
+        for moves in [&[][..], &[0, 1, 0, 1, 0], &[1, 2, 1, 2], &[0, 0, 3, 3, 1]] {
+            let game = ConnectFour::<BitState>::from_moves(4, 4, moves);
+            let (move_, statistics) = player.analyse(&game);
+            let entries = tablebase.move_entries(&game).unwrap();
+            let entry = |move_| entries.iter().find(|&&(column, _)| column == move_).unwrap().1;
+            assert_eq!(entry(move_).value, tablebase.entry(&game).unwrap().value, "after {:?}", moves);
+            for statistics in statistics {
+                let expected = match entry(statistics.move_).value {
+                    Value::Win => 1.,
+                    Value::Draw => 0.5,
+                    Value::Loss => 0.,
+                };
+                assert_eq!(statistics.value, expected);
+            }
+        }
+        // Even beginners block reliably with an oracle, which is asked before anything else.
+        let game = ConnectFour::<BitState>::from_moves(4, 4, &[0, 1, 0, 1, 0]);
+        let player = DifficultyPlayer::new(Difficulty::Beginner).with_oracle(Arc::new(tablebase));
+        assert_eq!(player.analyse(&game), (0, vec![]));
+    }
+}
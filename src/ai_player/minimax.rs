@@ -0,0 +1,301 @@
+//! A classical baseline: negamax search with alpha-beta pruning and iterative deepening.
+//!
+//! The search only uses the `Game` trait, so it works for every game. Leaves at the depth
+//! limit are scored with a pluggable `Evaluate` implementation, finished games with the
+//! distance to the win or loss, so that faster wins are preferred.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{tactics, Evaluate, Game, Player, PlayerTrait, SearchInfo, Thought};
+
+/// The score of a position won at the root. Wins `n` plies away score `WIN - n`.
+const WIN: i32 = 1_000_000;
+
+/// The clock is only consulted every this many nodes.
+const NODES_PER_CLOCK_CHECK: u64 = 1_024;
+
+pub const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(1);
+
+
+/// Every position is even, so only the end of the game matters. Usable for any game, e. g.
+/// win lengths the heuristic evaluation doesn't support.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Neutral;
+
+impl<G: Game> Evaluate<G> for Neutral {
+    fn evaluate(&self, _game: &G, _player: Player) -> i32 {
+        0
+    }
+}
+
+
+#[derive(Clone)]
+pub struct MinimaxPlayer<G: Game> {
+    evaluation: Arc<dyn Evaluate<G>>,
+    max_depth: usize,
+    time_limit: Option<Duration>,
+}
+
+impl<G: Game> MinimaxPlayer<G> {
+    /// Searches deeper and deeper until `DEFAULT_TIME_LIMIT` has passed.
+    pub fn new(evaluation: impl Evaluate<G> + 'static) -> MinimaxPlayer<G> {
+        MinimaxPlayer {
+            evaluation: Arc::new(evaluation),
+            max_depth: usize::MAX,
+            time_limit: Some(DEFAULT_TIME_LIMIT),
+        }
+    }
+
+    pub fn with_depth(self, max_depth: usize) -> MinimaxPlayer<G> {
+        MinimaxPlayer { max_depth, ..self }
+    }
+
+    /// Without a time limit, the search always goes down to the maximal depth. With a time
+    /// limit, the result of the deepest completed iteration is used, but the first iteration
+    /// is always completed.
+    pub fn with_time_limit(self, time_limit: Option<Duration>) -> MinimaxPlayer<G> {
+        MinimaxPlayer { time_limit, ..self }
+    }
+
+    /// Returns all valid moves with their scores from the deepest completed iteration, best
+    /// move first, and the depth of that iteration. Only the score of the best move is exact,
+    /// the other scores are upper bounds.
+    pub fn search(&self, game: &G) -> (Vec<(G::Move, i32)>, usize) {
+        let (moves, depth, _) = self.search_until(game, &|| false);
+        (moves, depth)
+    }
+
+    /// Like `search`, but also stops once `stop` returns `true` and returns the number of
+    /// searched positions as well.
+    fn search_until(&self, game: &G, stop: &dyn Fn() -> bool) -> (Vec<(G::Move, i32)>, usize, u64) {
+        let mut search = Search {
+            evaluation: &*self.evaluation,
+            deadline: self.time_limit.map(|time_limit| Instant::now() + time_limit),
+            stop,
+            nodes: 0,
+            timed_out: false,
+            depth_limited: false,
+        };
+        let mut moves: Vec<_> = game.valid_moves().into_iter().map(|move_| (move_, 0)).collect();
+        let mut completed_depth = 0;
+        for depth in 1..=self.max_depth {
+            search.depth_limited = false;
+            let Some(scores) = search.root(game, &moves, depth, depth == 1) else {
+                break;
+            };
+            moves = scores;
+            completed_depth = depth;
+            log::debug!("depth {}: best move {:?} with score {}, {} nodes", depth, moves[0].0, moves[0].1, search.nodes);
+            if !search.depth_limited || moves[0].1.abs() >= WIN - depth as i32 {
+                // The result is exact, searching deeper does not change it.
+                break;
+            }
+        }
+        (moves, completed_depth, search.nodes)
+    }
+}
+
+impl<G: Game> PlayerTrait for MinimaxPlayer<G> {
+    type Game = G;
+
+    fn make_move(&self, game: &G) -> G::Move {
+        self.think(game, &|| false).0.0
+    }
+
+    fn think(&self, game: &G, stop: &dyn Fn() -> bool) -> Thought<G::Move> {
+        let mut info = None;
+        // Shallow searches under a tight time limit can miss that a move allows a win on top.
+        let (move_, _) = tactics::safety_net(game, |game| {
+            let (moves, depth, nodes) = self.search_until(game, stop);
+            let (move_, score) = *moves.first().expect("Could not find valid move");
+            // Only proven results can be expressed as expected results.
+            let value = if score >= WIN - depth as i32 {
+                Some(1.)
+            }
+            else if score <= -(WIN - depth as i32) {
+                Some(0.)
+            }
+            else {
+                None
+            };
+            info = Some(SearchInfo { depth, visits: nodes, value, principal_variation: vec![move_], report: None });
+            (move_, vec![])
+        });
+        let info = info
+            .filter(|info| info.principal_variation[0] == move_)
+            .unwrap_or_else(|| SearchInfo::from_statistics(move_, &[]));
+        ((move_, vec![]), info)
+    }
+}
+
+
+/// The winner of `game` with perfect play, `None` for a draw.
+pub(super) fn solve<G: Game>(game: &G) -> Option<Player> {
+    solve_until(game, &|| false).expect("searches that are never stopped finish")
+}
+
+/// Solves `game` like `solve`, but gives up with `None` once `stop` returns `true`.
+pub(super) fn solve_until<G: Game>(game: &G, stop: &dyn Fn() -> bool) -> Option<Option<Player>> {
+    let mut search = Search {
+        evaluation: &Unlimited,
+        deadline: None,
+        stop,
+        nodes: 0,
+        timed_out: false,
+        depth_limited: false,
+    };
+    // Only the sign of the score matters, so the narrowest window around a draw suffices.
+    let score = search.negamax(game, usize::MAX, -1, 1, 0);
+    if search.timed_out {
+        return None;
+    }
+    Some(if score > 0 {
+        Some(game.current_player())
+    }
+    else if score < 0 {
+        let mut opponent_to_move = game.clone();
+        opponent_to_move.next_player();
+        Some(opponent_to_move.current_player())
+    }
+    else {
+        None
+    })
+}
+
+/// Searches without a depth limit only evaluate positions once they have been stopped, and
+/// then their result is discarded.
+struct Unlimited;
+
+impl<G: Game> Evaluate<G> for Unlimited {
+    fn evaluate(&self, _game: &G, _player: Player) -> i32 {
+        0
+    }
+}
+
+
+struct Search<'a, G: Game> {
+    evaluation: &'a dyn Evaluate<G>,
+    deadline: Option<Instant>,
+    stop: &'a dyn Fn() -> bool,
+    nodes: u64,
+    timed_out: bool,
+    /// Whether any leaf was scored by the evaluation instead of the end of the game.
+    depth_limited: bool,
+}
+
+impl<G: Game> Search<'_, G> {
+    /// Scores all root moves, trying them in the given order. Returns `None` if the time ran
+    /// out, unless `must_complete` is set.
+    fn root(&mut self, game: &G, moves: &[(G::Move, i32)], depth: usize, must_complete: bool) -> Option<Vec<(G::Move, i32)>> {
+        let mut alpha = -WIN - 1;
+        let mut scores = Vec::with_capacity(moves.len());
+        for &(move_, _) in moves {
+            let mut child = game.clone();
+            child.play(move_).unwrap_or_else(|err| panic!("tried to play invalid move: {:?}", err));
+            let score = -self.negamax(&child, depth - 1, -WIN - 1, -alpha, 1);
+            if self.timed_out && !must_complete {
+                return None;
+            }
+            alpha = alpha.max(score);
+            scores.push((move_, score));
+        }
+        // The sort is stable, so moves with equal bounds keep their order from the last
+        // iteration.
+        scores.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        Some(scores)
+    }
+
+    fn negamax(&mut self, game: &G, depth: usize, mut alpha: i32, beta: i32, ply: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_PER_CLOCK_CHECK) {
+            if let Some(deadline) = self.deadline {
+                self.timed_out |= Instant::now() >= deadline;
+            }
+            self.timed_out |= (self.stop)();
+        }
+        if let Some(winner) = game.winner() {
+            return if winner == game.current_player() { WIN - ply } else { -(WIN - ply) };
+        }
+        if game.has_ended() {
+            return 0;
+        }
+        if depth == 0 || self.timed_out {
+            self.depth_limited = true;
+            return self.evaluation.evaluate(game, game.current_player());
+        }
+
+        let mut best = -WIN - 1;
+        for move_ in game.valid_moves() {
+            let mut child = game.clone();
+            child.play(move_).unwrap_or_else(|err| panic!("tried to play invalid move: {:?}", err));
+            let score = -self.negamax(&child, depth - 1, -beta, -alpha, ply + 1);
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connect_four::eval::Evaluator;
+    use crate::{BitState, ConnectFour, VecState};
+
+    /// Plain negamax without pruning.
+    fn reference_score<G: Game>(game: &G, depth: usize, ply: i32, evaluation: &dyn Evaluate<G>) -> i32 {
+        if let Some(winner) = game.winner() {
+            return if winner == game.current_player() { WIN - ply } else { -(WIN - ply) };
+        }
+        if game.has_ended() {
+            return 0;
+        }
+        if depth == 0 {
+            return evaluation.evaluate(game, game.current_player());
+        }
+        game.valid_moves()
+            .into_iter()
+            .map(|move_| {
+                let mut child = game.clone();
+                child.play(move_).unwrap();
+                -reference_score(&child, depth - 1, ply + 1, evaluation)
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn takes_wins_and_blocks_threats_in_any_game() {
+        let player = MinimaxPlayer::new(Neutral).with_depth(2).with_time_limit(None);
+        assert_eq!(player.make_move(&ConnectFour::<VecState>::from_moves(7, 6, &[0, 6, 0, 6, 0, 6])), 0);
+        assert_eq!(player.make_move(&ConnectFour::<VecState>::from_moves(7, 6, &[0, 6, 0, 6, 0])), 0);
+    }
+
+    #[test]
+    fn pruning_does_not_change_the_best_score() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[3, 3, 2, 4, 4]);
+        let evaluator = Evaluator::for_state(game.state());
+        for depth in 1..=4 {
+            let player = MinimaxPlayer::new(evaluator.clone()).with_depth(depth).with_time_limit(None);
+            let (moves, completed_depth) = player.search(&game);
+            assert_eq!(completed_depth, depth);
+            assert_eq!(moves[0].1, reference_score(&game, depth, 0, &evaluator));
+        }
+    }
+
+    #[test]
+    fn respects_the_time_limit() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[]);
+        let player = MinimaxPlayer::new(Evaluator::for_state(game.state())).with_time_limit(Some(Duration::from_millis(100)));
+        let start = Instant::now();
+        let (moves, depth) = player.search(&game);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(depth >= 1);
+        assert_eq!(moves.len(), 7);
+    }
+}
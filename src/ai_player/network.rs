@@ -0,0 +1,431 @@
+//! A small multilayer perceptron with a policy and a value head, trained with plain stochastic
+//! gradient descent on the CPU.
+//!
+//! The input encodes the board from the point of view of the player to move: one plane with
+//! their own stones and one with the opponent’s stones. A trunk of fully connected ReLU layers
+//! feeds two heads, the policy head with one logit per column and the value head with the
+//! logits of a win, a draw and a loss of the player to move.
+
+use std::io;
+use std::io::prelude::*;
+
+use rand::Rng;
+
+use crate::persist::{invalid_data, LoadError, Persist};
+use crate::{ConnectFour, Game, Player, State};
+
+const NETWORK_MAGIC: &[u8; 8] = b"MCC4NNET";
+const NETWORK_FORMAT_VERSION: u16 = 1;
+
+/// The indices of the value head outputs.
+pub(crate) const WIN: usize = 0;
+pub(crate) const DRAW: usize = 1;
+pub(crate) const LOSS: usize = 2;
+
+
+/// A fully connected layer with `outputs × inputs` weights in row major order.
+#[derive(Clone, Debug, PartialEq)]
+struct Layer {
+    inputs: usize,
+    outputs: usize,
+    weights: Vec<f64>,
+    biases: Vec<f64>,
+}
+
+impl Layer {
+    fn zeros(inputs: usize, outputs: usize) -> Layer {
+        Layer { inputs, outputs, weights: vec![0.; inputs * outputs], biases: vec![0.; outputs] }
+    }
+
+    /// He initialisation, suitable for ReLU activations.
+    fn random(inputs: usize, outputs: usize, rng: &mut impl Rng) -> Layer {
+        let bound = (6. / inputs as f64).sqrt();
+        Layer {
+            weights: (0..inputs * outputs).map(|_| rng.gen_range(-bound, bound)).collect(),
+            ..Layer::zeros(inputs, outputs)
+        }
+    }
+
+    fn forward(&self, input: &[f64]) -> Vec<f64> {
+        self.weights.chunks(self.inputs)
+            .zip(&self.biases)
+            .map(|(row, bias)| bias + row.iter().zip(input).map(|(w, x)| w * x).sum::<f64>())
+            .collect()
+    }
+
+    /// Adds the gradient of the weights to `gradient` and returns the gradient of the input.
+    fn backward(&self, input: &[f64], output_gradient: &[f64], gradient: &mut Layer) -> Vec<f64> {
+        let mut input_gradient = vec![0.; self.inputs];
+        for (output, &delta) in output_gradient.iter().enumerate() {
+            let row = output * self.inputs..(output + 1) * self.inputs;
+            for ((w, dw), (x, dx)) in self.weights[row.clone()].iter()
+                .zip(&mut gradient.weights[row])
+                .zip(input.iter().zip(&mut input_gradient))
+            {
+                *dw += delta * x;
+                *dx += delta * w;
+            }
+            gradient.biases[output] += delta;
+        }
+        input_gradient
+    }
+
+    /// Descends along `gradient`, which is summed over `batch_size` samples.
+    fn update(&mut self, gradient: &Layer, batch_size: usize, learning_rate: f64, weight_decay: f64) {
+        let scale = 1. / batch_size as f64;
+        for (w, dw) in self.weights.iter_mut().zip(&gradient.weights) {
+            *w -= learning_rate * (scale * dw + weight_decay * *w);
+        }
+        for (b, db) in self.biases.iter_mut().zip(&gradient.biases) {
+            *b -= learning_rate * scale * db;
+        }
+    }
+}
+
+impl Persist for Layer {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.inputs.write_to(writer)?;
+        self.outputs.write_to(writer)?;
+        for value in self.weights.iter().chain(&self.biases) {
+            value.write_to(writer)?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let inputs = usize::read_from(reader)?;
+        let outputs = usize::read_from(reader)?;
+        if inputs == 0 || outputs == 0 || inputs.checked_mul(outputs).is_none_or(|size| size > 1 << 24) {
+            return Err(invalid_data("invalid layer size"));
+        }
+        let mut layer = Layer::zeros(inputs, outputs);
+        for value in layer.weights.iter_mut().chain(&mut layer.biases) {
+            *value = f64::read_from(reader)?;
+        }
+        Ok(layer)
+    }
+}
+
+
+fn relu(values: &mut [f64]) {
+    values.iter_mut().for_each(|value| *value = value.max(0.));
+}
+
+fn softmax(logits: &[f64]) -> Vec<f64> {
+    let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<_> = logits.iter().map(|logit| (logit - max).exp()).collect();
+    let total: f64 = exps.iter().sum();
+    exps.into_iter().map(|exp| exp / total).collect()
+}
+
+fn cross_entropy(probabilities: &[f64], targets: &[f64]) -> f64 {
+    probabilities.iter()
+        .zip(targets)
+        .filter(|&(_, &target)| target > 0.)
+        .map(|(probability, target)| -target * probability.max(1e-12).ln())
+        .sum()
+}
+
+
+/// The output of the network for one position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prediction {
+    /// The probability of each column being the best move, including full columns.
+    pub policy: Vec<f64>,
+    /// The probabilities of a win, a draw and a loss of the player to move, in this order.
+    pub value: [f64; 3],
+}
+
+impl Prediction {
+    /// The expected result for the player to move, counting draws as half a win.
+    pub fn expected_result(&self) -> f64 {
+        self.value[WIN] + self.value[DRAW] / 2.
+    }
+}
+
+
+/// A training example: an encoded position with the targets for both heads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub input: Vec<f64>,
+    /// The target move distribution, one entry per column.
+    pub policy: Vec<f64>,
+    /// The target probabilities of a win, a draw and a loss of the player to move, usually
+    /// the one-hot encoded result of the game.
+    pub value: [f64; 3],
+}
+
+impl Sample {
+    /// The same position reflected at the centre column. Connect Four is symmetric, so this
+    /// doubles the training data for free.
+    pub fn mirrored(&self, columns: usize, rows: usize) -> Sample {
+        let mut input = self.input.clone();
+        for (plane, mirrored_plane) in self.input.chunks(columns * rows).zip(input.chunks_mut(columns * rows)) {
+            for (column, mirrored_column) in plane.chunks(rows).rev().zip(mirrored_plane.chunks_mut(rows)) {
+                mirrored_column.copy_from_slice(column);
+            }
+        }
+        Sample {
+            input,
+            policy: self.policy.iter().rev().cloned().collect(),
+            value: self.value,
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    columns: usize,
+    rows: usize,
+    trunk: Vec<Layer>,
+    policy: Layer,
+    value: Layer,
+}
+
+impl Network {
+    /// A randomly initialised network for boards of the given size, with one hidden layer per
+    /// entry of `hidden_layers`.
+    pub fn new(columns: usize, rows: usize, hidden_layers: &[usize], rng: &mut impl Rng) -> Network {
+        let mut inputs = 2 * columns * rows;
+        let mut trunk = vec![];
+        for &outputs in hidden_layers {
+            trunk.push(Layer::random(inputs, outputs, rng));
+            inputs = outputs;
+        }
+        Network {
+            columns,
+            rows,
+            trunk,
+            policy: Layer::random(inputs, columns, rng),
+            value: Layer::random(inputs, 3, rng),
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    /// Encodes `game` from the point of view of the player to move.
+    pub fn encode<S: State>(&self, game: &ConnectFour<S>) -> Vec<f64> {
+        assert_eq!(game.size(), self.size(), "the network was built for another board size");
+        let me = game.current_player();
+        let plane = self.columns * self.rows;
+        let mut input = vec![0.; 2 * plane];
+        for column in 0..self.columns {
+            for row in 0..self.rows {
+                let offset = match game.state().get(column, row) {
+                    Player(0) => continue,
+                    player if player == me => 0,
+                    _ => plane,
+                };
+                input[offset + column * self.rows + row] = 1.;
+            }
+        }
+        input
+    }
+
+    pub fn predict<S: State>(&self, game: &ConnectFour<S>) -> Prediction {
+        self.predict_encoded(&self.encode(game))
+    }
+
+    pub fn predict_encoded(&self, input: &[f64]) -> Prediction {
+        let features = self.trunk.iter()
+            .fold(input.to_vec(), |input, layer| {
+                let mut output = layer.forward(&input);
+                relu(&mut output);
+                output
+            });
+        let value = softmax(&self.value.forward(&features));
+        Prediction {
+            policy: softmax(&self.policy.forward(&features)),
+            value: [value[WIN], value[DRAW], value[LOSS]],
+        }
+    }
+
+    /// The mean cross entropy loss of both heads over `samples`.
+    pub fn loss(&self, samples: &[Sample]) -> f64 {
+        let total: f64 = samples.iter()
+            .map(|sample| {
+                let prediction = self.predict_encoded(&sample.input);
+                cross_entropy(&prediction.policy, &sample.policy) + cross_entropy(&prediction.value, &sample.value)
+            })
+            .sum();
+        total / samples.len() as f64
+    }
+
+    /// Performs one gradient descent step on the mean loss over the minibatch `samples`, with
+    /// L2 regularisation of the weights.
+    pub fn train(&mut self, samples: &[Sample], learning_rate: f64, weight_decay: f64) {
+        if samples.is_empty() {
+            return;
+        }
+        let mut trunk_gradient: Vec<_> = self.trunk.iter().map(|layer| Layer::zeros(layer.inputs, layer.outputs)).collect();
+        let mut policy_gradient = Layer::zeros(self.policy.inputs, self.policy.outputs);
+        let mut value_gradient = Layer::zeros(self.value.inputs, self.value.outputs);
+
+        for sample in samples {
+            let mut activations = vec![sample.input.clone()];
+            for layer in &self.trunk {
+                let mut output = layer.forward(activations.last().unwrap());
+                relu(&mut output);
+                activations.push(output);
+            }
+            let features = activations.last().unwrap();
+
+            // The gradient of the cross entropy of a softmax is `probabilities - targets`.
+            let policy_delta: Vec<_> = softmax(&self.policy.forward(features)).iter()
+                .zip(&sample.policy)
+                .map(|(probability, target)| probability - target)
+                .collect();
+            let value_delta: Vec<_> = softmax(&self.value.forward(features)).iter()
+                .zip(&sample.value)
+                .map(|(probability, target)| probability - target)
+                .collect();
+            let mut delta: Vec<_> = self.policy.backward(features, &policy_delta, &mut policy_gradient)
+                .into_iter()
+                .zip(self.value.backward(features, &value_delta, &mut value_gradient))
+                .map(|(policy, value)| policy + value)
+                .collect();
+
+            for ((layer, gradient), (input, output)) in self.trunk.iter()
+                .zip(&mut trunk_gradient)
+                .zip(activations.iter().zip(&activations[1..]))
+                .rev()
+            {
+                delta.iter_mut()
+                    .zip(output)
+                    .filter(|&(_, &activation)| activation <= 0.)
+                    .for_each(|(delta, _)| *delta = 0.);
+                delta = layer.backward(input, &delta, gradient);
+            }
+        }
+
+        for (layer, gradient) in self.trunk.iter_mut().zip(&trunk_gradient) {
+            layer.update(gradient, samples.len(), learning_rate, weight_decay);
+        }
+        self.policy.update(&policy_gradient, samples.len(), learning_rate, weight_decay);
+        self.value.update(&value_gradient, samples.len(), learning_rate, weight_decay);
+    }
+}
+
+
+/// The file format consists of the magic bytes `MCC4NNET`, the format version, the board size
+/// and the layers: the number of trunk layers, the trunk layers, the policy head and the value
+/// head. Each layer stores its input and output count followed by its weights and biases.
+impl Network {
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(NETWORK_MAGIC)?;
+        NETWORK_FORMAT_VERSION.write_to(writer)?;
+        self.columns.write_to(writer)?;
+        self.rows.write_to(writer)?;
+        (self.trunk.len() as u32).write_to(writer)?;
+        for layer in &self.trunk {
+            layer.write_to(writer)?;
+        }
+        self.policy.write_to(writer)?;
+        self.value.write_to(writer)
+    }
+
+    pub fn load<R: Read>(reader: &mut R) -> Result<Network, LoadError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != NETWORK_MAGIC {
+            return Err(LoadError::WrongFileType);
+        }
+        let version = u16::read_from(reader)?;
+        if version != NETWORK_FORMAT_VERSION {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+        let columns = usize::read_from(reader)?;
+        let rows = usize::read_from(reader)?;
+        let trunk = (0..u32::read_from(reader)?)
+            .map(|_| Layer::read_from(reader))
+            .collect::<io::Result<Vec<_>>>()?;
+        let policy = Layer::read_from(reader)?;
+        let value = Layer::read_from(reader)?;
+
+        let mut inputs = columns.checked_mul(rows)
+            .and_then(|cells| cells.checked_mul(2))
+            .ok_or(LoadError::Inconsistent("invalid board size"))?;
+        for layer in &trunk {
+            if layer.inputs != inputs {
+                return Err(LoadError::Inconsistent("layer sizes do not match"));
+            }
+            inputs = layer.outputs;
+        }
+        if policy.inputs != inputs || value.inputs != inputs || policy.outputs != columns || value.outputs != 3 {
+            return Err(LoadError::Inconsistent("head sizes do not match the board"));
+        }
+        Ok(Network { columns, rows, trunk, policy, value })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_player::new_rng;
+    use crate::BitState;
+
+    fn sample(network: &Network) -> Sample {
+        let mut game = ConnectFour::<BitState>::new(7, 6).unwrap();
+        game.play(3).unwrap();
+        Sample {
+            input: network.encode(&game),
+            policy: vec![0., 0., 0.25, 0.75, 0., 0., 0.],
+            value: [0., 0., 1.],
+        }
+    }
+
+    #[test]
+    fn predictions_are_distributions() {
+        let network = Network::new(7, 6, &[32, 16], &mut new_rng());
+        let prediction = network.predict(&ConnectFour::<BitState>::new(7, 6).unwrap());
+        assert_eq!(prediction.policy.len(), 7);
+        assert!((prediction.policy.iter().sum::<f64>() - 1.).abs() < 1e-9);
+        assert!((prediction.value.iter().sum::<f64>() - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn training_reduces_the_loss() {
+        let mut network = Network::new(7, 6, &[32, 16], &mut new_rng());
+        let samples = vec![sample(&network)];
+        let initial_loss = network.loss(&samples);
+        for _ in 0..100 {
+            network.train(&samples, 0.05, 1e-4);
+        }
+        assert!(network.loss(&samples) < initial_loss / 2., "{} -> {}", initial_loss, network.loss(&samples));
+        let prediction = network.predict_encoded(&samples[0].input);
+        assert!(prediction.value[LOSS] > 0.8);
+        assert!(prediction.policy[3] > prediction.policy[2]);
+    }
+
+    #[test]
+    fn saved_networks_can_be_loaded() {
+        let network = Network::new(5, 4, &[8], &mut new_rng());
+        let mut file = vec![];
+        network.save(&mut file).unwrap();
+        assert_eq!(Network::load(&mut &file[..]).unwrap(), network);
+        file[0] = b'X';
+        assert!(matches!(Network::load(&mut &file[..]), Err(LoadError::WrongFileType)));
+
+        // The board size follows the magic bytes and the version.
+        file[0] = b'M';
+        file[10..26].copy_from_slice(&[0xFF; 16]);
+        assert!(matches!(Network::load(&mut &file[..]), Err(LoadError::Inconsistent(_))));
+    }
+
+    #[test]
+    fn mirrored_samples_reflect_the_board() {
+        let network = Network::new(7, 6, &[8], &mut new_rng());
+        let play = |column| {
+            let mut game = ConnectFour::<BitState>::new(7, 6).unwrap();
+            game.play(column).unwrap();
+            Sample { input: network.encode(&game), policy: vec![0., 0., 0.25, 0.75, 0., 0., 0.], value: [1., 0., 0.] }
+        };
+        let mirrored = play(1).mirrored(7, 6);
+        assert_eq!(mirrored.input, play(5).input);
+        assert_eq!(mirrored.policy, vec![0., 0., 0., 0.75, 0.25, 0., 0.]);
+    }
+}
@@ -0,0 +1,255 @@
+//! Playout policies for the Monte Carlo players.
+//!
+//! A playout policy plays a position to the end (or estimates how it would end) and reports
+//! the winner. Better informed playouts give more accurate results per playout, but are
+//! slower than uniformly random ones.
+
+use rand::Rng;
+
+use super::{choose, random_playout, rand_in_range, PlayoutRng};
+use crate::connect_four::eval::{Evaluator, WIN_SCORE};
+use crate::{tactics, BitState, ConnectFour, Evaluate, Game, Player, State};
+
+
+pub trait PlayoutPolicy<G: Game>: Send + Sync {
+    /// Plays `game` until it has ended and returns the winner, `None` for a draw.
+    fn playout(&self, rng: &mut PlayoutRng, game: G) -> Option<Player>;
+
+    /// The result of a playout from `game` as the tree search backs it up. Policies that
+    /// estimate the probabilities of the results, instead of playing the game out, return
+    /// their estimate, so that the search doesn't add the noise of sampling from it.
+    fn expected_result(&self, rng: &mut PlayoutRng, game: G) -> Expectation {
+        let player = game.current_player();
+        Expectation::of(self.playout(rng, game), player)
+    }
+}
+
+
+/// The probabilities with which `player` wins and draws a game. The opponent wins otherwise.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Expectation {
+    pub player: Player,
+    pub win: f64,
+    pub draw: f64,
+}
+
+impl Expectation {
+    /// The certain result of a finished game, `None` for a draw.
+    pub fn of(winner: Option<Player>, player: Player) -> Expectation {
+        match winner {
+            Some(winner) if winner == player => Expectation { player, win: 1., draw: 0. },
+            Some(_) => Expectation { player, win: 0., draw: 0. },
+            None => Expectation { player, win: 0., draw: 1. },
+        }
+    }
+
+    /// The probabilities with which `me` wins and draws, in a game of two players.
+    pub fn for_player(&self, me: Player) -> (f64, f64) {
+        if me == self.player {
+            (self.win, self.draw)
+        }
+        else {
+            (1. - self.win - self.draw, self.draw)
+        }
+    }
+}
+
+
+/// Both players choose uniformly among all valid moves.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UniformPlayout;
+
+impl<G: Game> PlayoutPolicy<G> for UniformPlayout {
+    fn playout(&self, rng: &mut PlayoutRng, game: G) -> Option<Player> {
+        random_playout(rng, game)
+    }
+}
+
+
+/// Columns are chosen with probabilities proportional to their closeness to the centre.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CentreWeightedPlayout;
+
+impl<S: State> PlayoutPolicy<ConnectFour<S>> for CentreWeightedPlayout {
+    fn playout(&self, rng: &mut PlayoutRng, mut game: ConnectFour<S>) -> Option<Player> {
+        if game.has_ended() {
+            return game.winner();
+        }
+        let columns = game.size().0;
+        let weight = |column: usize| (columns - (2 * column as isize - (columns as isize - 1)).unsigned_abs()) as u32;
+        let mut valid_moves = vec![];
+        loop {
+            game.valid_moves_fast(&mut valid_moves);
+            if valid_moves.is_empty() {
+                return None;
+            }
+            let total: u32 = valid_moves.iter().map(|&column| weight(column)).sum();
+            let mut remaining = rand_in_range(total, rng);
+            let column = *valid_moves.iter()
+                .find(|&&column| {
+                    let found = remaining < weight(column);
+                    remaining = remaining.wrapping_sub(weight(column));
+                    found
+                })
+                .expect("weights add up to `total`");
+            if let Some(winner) = game.play(column).unwrap() {
+                return Some(winner);
+            }
+        }
+    }
+}
+
+
+/// Both players take immediate wins and block the opponent’s immediate wins, and play
+/// randomly otherwise.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TacticalPlayout;
+
+impl<G: Game> PlayoutPolicy<G> for TacticalPlayout {
+    fn playout(&self, rng: &mut PlayoutRng, mut game: G) -> Option<Player> {
+        if game.has_ended() {
+            return game.winner();
+        }
+        let mut valid_moves = vec![];
+        loop {
+            game.valid_moves_fast(&mut valid_moves);
+            if valid_moves.is_empty() {
+                return None;
+            }
+            let move_ = tactics::forced_move(&game).unwrap_or_else(|| *choose(rng, &valid_moves));
+            if let Some(winner) = game.play(move_).unwrap() {
+                return Some(winner);
+            }
+        }
+    }
+}
+
+
+/// Plays at most `plies` random moves and then estimates the result with a static evaluation.
+///
+/// The evaluation `score` is turned into the probability `1 / (1 + exp(-score / scale))` that
+/// the player to move wins. Playouts draw the winner accordingly, while the tree search backs
+/// up the probability itself.
+#[derive(Clone, Debug)]
+pub struct EvaluationCutoffPlayout<E> {
+    evaluation: E,
+    plies: usize,
+    scale: f64,
+}
+
+impl<E> EvaluationCutoffPlayout<E> {
+    pub fn new(evaluation: E, plies: usize, scale: f64) -> EvaluationCutoffPlayout<E> {
+        EvaluationCutoffPlayout { evaluation, plies, scale }
+    }
+}
+
+impl EvaluationCutoffPlayout<Evaluator> {
+    /// Uses the heuristic evaluation from `connect_four::eval`.
+    pub fn heuristic(game: &ConnectFour<BitState>, plies: usize) -> EvaluationCutoffPlayout<Evaluator> {
+        Self::new(Evaluator::for_state(game.state()), plies, WIN_SCORE as f64 / 50.)
+    }
+}
+
+impl<E> EvaluationCutoffPlayout<E> {
+    /// Plays the random moves and returns the result if the game ended, or else the estimated
+    /// probability that the player to move wins.
+    fn cut_off<G: Game>(&self, rng: &mut PlayoutRng, mut game: G) -> Expectation where E: Evaluate<G> {
+        let player = game.current_player();
+        if game.has_ended() {
+            return Expectation::of(game.winner(), player);
+        }
+        let mut valid_moves = vec![];
+        for _ in 0..self.plies {
+            game.valid_moves_fast(&mut valid_moves);
+            if valid_moves.is_empty() {
+                return Expectation::of(None, player);
+            }
+            if let Some(winner) = game.play(*choose(rng, &valid_moves)).unwrap() {
+                return Expectation::of(Some(winner), player);
+            }
+        }
+        if game.has_ended() {
+            return Expectation::of(game.winner(), player);
+        }
+
+        let to_move = game.current_player();
+        let score = self.evaluation.evaluate(&game, to_move) as f64;
+        Expectation { player: to_move, win: 1. / (1. + (-score / self.scale).exp()), draw: 0. }
+    }
+}
+
+impl<G: Game, E: Evaluate<G>> PlayoutPolicy<G> for EvaluationCutoffPlayout<E> {
+    fn playout(&self, rng: &mut PlayoutRng, game: G) -> Option<Player> {
+        let me = game.current_player();
+        let mut waiting = game.clone();
+        waiting.next_player();
+        let (win, draw) = self.cut_off(rng, game).for_player(me);
+        let roll: f64 = rng.gen();
+        if roll < win {
+            Some(me)
+        }
+        else if roll < win + draw {
+            None
+        }
+        else {
+            Some(waiting.current_player())
+        }
+    }
+
+    /// The estimated probability itself, without drawing a winner from it.
+    fn expected_result(&self, rng: &mut PlayoutRng, game: G) -> Expectation {
+        self.cut_off(rng, game)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_player::new_rng;
+    use crate::{Allocation, MonteCarloPlayer, PlayerTrait, SearchConfig, TreeSearchPlayer};
+
+    #[test]
+    fn tactical_playouts_take_immediate_wins() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[0, 6, 0, 6, 0, 6]);
+        let mut rng = new_rng();
+        for _ in 0..100 {
+            assert_eq!(TacticalPlayout.playout(&mut rng, game.clone()), Some(Player(1)));
+        }
+    }
+
+    #[test]
+    fn cutoff_playouts_follow_the_evaluation() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[0, 6, 0, 6, 0, 6]);
+        let policy = EvaluationCutoffPlayout::heuristic(&game, 0);
+        let mut rng = new_rng();
+        for _ in 0..100 {
+            assert_eq!(policy.playout(&mut rng, game.clone()), Some(Player(1)));
+        }
+    }
+
+    #[test]
+    fn cutoff_results_are_the_estimated_probability() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[3, 3, 2]);
+        let policy = EvaluationCutoffPlayout::heuristic(&game, 0);
+        let expectation = policy.expected_result(&mut new_rng(), game.clone());
+        assert_eq!(expectation.player, Player(2));
+        assert!(0. < expectation.win && expectation.win < 0.5, "{:?}", expectation);
+        assert_eq!(expectation.draw, 0.);
+        assert_eq!(policy.expected_result(&mut new_rng(), game.clone()), expectation);
+    }
+
+    #[test]
+    fn players_accept_a_playout_policy() {
+        // Nothing is forced, but extending the first player's two stones in the bottom row to
+        // either side threatens to win on both ends.
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[2, 2, 3, 3]);
+        assert_eq!(tactics::forced_move(&game), None);
+        let player = MonteCarloPlayer::with_budget(200, Allocation::Uniform)
+            .with_playout(TacticalPlayout);
+        assert!([1, 4].contains(&player.make_move(&game)));
+        let config = SearchConfig { simulations: 500, ..SearchConfig::default() };
+        let player = TreeSearchPlayer::with_config(config).with_playout(CentreWeightedPlayout);
+        assert!([1, 4].contains(&player.make_move(&game)));
+    }
+}
@@ -0,0 +1,100 @@
+//! Prior knowledge about moves for guiding the tree search.
+//!
+//! When a node of the search tree is created, the prior assigns a weight to each of its moves.
+//! Unexpanded moves are expanded in the order of their weights, and selection adds a
+//! progressive bias term `prior_weight * prior / (visits + 1)` to the UCB value, so that the
+//! prior dominates the first few visits and fades as real statistics accumulate.
+
+use crate::connect_four::eval::{Evaluator, WIN_SCORE};
+use crate::{BitState, ConnectFour, Game, State};
+
+
+pub trait Prior<G: Game>: Send + Sync {
+    /// Returns a non-negative weight for every move in `moves`, higher weights mark more
+    /// promising moves. The weights are normalised by the caller.
+    fn priors(&self, game: &G, moves: &[G::Move]) -> Vec<f64>;
+}
+
+
+/// No prior knowledge, all moves are equally promising.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UniformPrior;
+
+impl<G: Game> Prior<G> for UniformPrior {
+    fn priors(&self, _game: &G, moves: &[G::Move]) -> Vec<f64> {
+        vec![1.; moves.len()]
+    }
+}
+
+
+/// Prefers columns close to the centre of the board.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CentrePrior;
+
+impl<S: State> Prior<ConnectFour<S>> for CentrePrior {
+    fn priors(&self, game: &ConnectFour<S>, moves: &[usize]) -> Vec<f64> {
+        let columns = game.size().0 as f64;
+        moves.iter()
+            .map(|&column| columns - (2. * column as f64 - (columns - 1.)).abs())
+            .collect()
+    }
+}
+
+
+/// Weights moves by the static evaluation of the resulting positions.
+#[derive(Clone, Debug)]
+pub struct HeuristicPrior {
+    evaluator: Evaluator,
+    temperature: f64,
+}
+
+impl HeuristicPrior {
+    pub fn new(game: &ConnectFour<BitState>) -> HeuristicPrior {
+        HeuristicPrior {
+            evaluator: Evaluator::for_state(game.state()),
+            temperature: 10.,
+        }
+    }
+
+    /// Scores are turned into weights by `exp(score / temperature)`, so lower temperatures make
+    /// the prior more decisive.
+    pub fn with_temperature(self, temperature: f64) -> HeuristicPrior {
+        HeuristicPrior { temperature, ..self }
+    }
+}
+
+impl Prior<ConnectFour<BitState>> for HeuristicPrior {
+    fn priors(&self, game: &ConnectFour<BitState>, moves: &[usize]) -> Vec<f64> {
+        let me = game.current_player();
+        let scores: Vec<_> = moves.iter()
+            .map(|&column| {
+                let mut game = game.clone();
+                match game.play(column) {
+                    Ok(Some(_)) => 2 * WIN_SCORE,
+                    Ok(None) => self.evaluator.evaluate(game.state(), me),
+                    Err(_) => -2 * WIN_SCORE,
+                }
+            })
+            .collect();
+        let best = scores.iter().cloned().max().unwrap_or(0);
+        scores.into_iter()
+            .map(|score| ((score - best) as f64 / self.temperature).exp())
+            .collect()
+    }
+}
+
+
+/// Computes the priors for the moves of `game`, normalised to sum up to one.
+pub(super) fn normalised_priors<G: Game>(prior: &dyn Prior<G>, game: &G, moves: &[G::Move]) -> Vec<f64> {
+    if moves.is_empty() {
+        return vec![];
+    }
+    let priors = prior.priors(game, moves);
+    let sum: f64 = priors.iter().sum();
+    if sum > 0. && sum.is_finite() {
+        priors.into_iter().map(|prior| prior / sum).collect()
+    }
+    else {
+        vec![1. / moves.len() as f64; moves.len()]
+    }
+}
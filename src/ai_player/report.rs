@@ -0,0 +1,189 @@
+//! Statistics about the playouts of the `MonteCarloPlayer`.
+//!
+//! Every playout ends in a win, a draw or a loss, so the rates of these outcomes are binomial
+//! proportions. They are reported with Wilson score intervals, which stay within `[0, 1]` and
+//! remain sensible for small samples and rates close to 0 or 1. The value of a move is the
+//! mean of the outcome weights, scaled so that a loss is worth 0 and a win 1, and is treated
+//! as a proportion in the same way.
+
+use std::fmt;
+
+use super::Outcomes;
+
+/// `z` for a two-sided 95 % confidence interval.
+pub const CONFIDENCE_Z: f64 = 1.96;
+
+
+/// How much a win, a draw and a loss are worth when the moves are ranked.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutcomeWeights {
+    pub win: f64,
+    pub draw: f64,
+    pub loss: f64,
+}
+
+impl Default for OutcomeWeights {
+    /// The scores the player has always ranked its moves by: +2 for a win, +1 for a draw and
+    /// -2 for a loss, so a draw is worth 0.75 on the scale of `value`.
+    fn default() -> OutcomeWeights {
+        OutcomeWeights { win: 2., draw: 1., loss: -2. }
+    }
+}
+
+impl OutcomeWeights {
+    /// Panics unless a win is worth more than a loss and a draw lies in between.
+    pub fn new(win: f64, draw: f64, loss: f64) -> OutcomeWeights {
+        assert!(loss < win && loss <= draw && draw <= win, "weights must be ordered loss ≤ draw ≤ win with loss < win");
+        OutcomeWeights { win, draw, loss }
+    }
+
+    /// The mean weight of `outcomes`, scaled to `[0, 1]`. Moves without playouts are worth
+    /// as much as a draw.
+    pub fn value(&self, outcomes: &Outcomes) -> f64 {
+        let draw = (self.draw - self.loss) / (self.win - self.loss);
+        if outcomes.playouts() == 0 {
+            return draw;
+        }
+        (outcomes.wins as f64 + draw * outcomes.draws as f64) / outcomes.playouts() as f64
+    }
+}
+
+
+/// An estimated proportion with its confidence interval.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rate {
+    pub estimate: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Rate {
+    /// The Wilson score interval of `successes` out of `trials`. Successes may be fractional,
+    /// as for the value of a move. Without trials nothing is known and the interval is `[0, 1]`.
+    pub fn wilson(successes: f64, trials: u64) -> Rate {
+        if trials == 0 {
+            return Rate { estimate: 0., lower: 0., upper: 1. };
+        }
+        let n = trials as f64;
+        let p = successes / n;
+        let z2 = CONFIDENCE_Z * CONFIDENCE_Z;
+        let centre = (p + z2 / (2. * n)) / (1. + z2 / n);
+        let half_width = CONFIDENCE_Z / (1. + z2 / n) * (p * (1. - p) / n + z2 / (4. * n * n)).sqrt();
+        Rate {
+            estimate: p,
+            lower: (centre - half_width).max(0.),
+            upper: (centre + half_width).min(1.),
+        }
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:5.1} % [{:5.1}, {:5.1}]", 100. * self.estimate, 100. * self.lower, 100. * self.upper)
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MoveReport<M> {
+    pub move_: M,
+    pub outcomes: Outcomes,
+    pub wins: Rate,
+    pub draws: Rate,
+    pub losses: Rate,
+    /// The weighted value of the move, see `OutcomeWeights::value`.
+    pub value: Rate,
+}
+
+impl<M> MoveReport<M> {
+    pub fn new(move_: M, outcomes: Outcomes, weights: &OutcomeWeights) -> MoveReport<M> {
+        let playouts = outcomes.playouts();
+        let value = weights.value(&outcomes);
+        MoveReport {
+            move_,
+            outcomes,
+            wins: Rate::wilson(outcomes.wins as f64, playouts),
+            draws: Rate::wilson(outcomes.draws as f64, playouts),
+            losses: Rate::wilson(outcomes.losses as f64, playouts),
+            value: Rate { estimate: value, ..Rate::wilson(value * playouts as f64, playouts) },
+        }
+    }
+}
+
+
+/// The playout results of all moves, best move first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report<M> {
+    pub moves: Vec<MoveReport<M>>,
+}
+
+impl<M> Report<M> {
+    pub fn best_move(&self) -> Option<&MoveReport<M>> {
+        self.moves.first()
+    }
+
+    /// Whether the confidence interval of the value of the best move lies entirely above the
+    /// interval of the second best move. If not, the second best move may well be at least as
+    /// good and more playouts are needed to tell them apart. A single move is always separated.
+    pub fn is_separated(&self) -> bool {
+        match &self.moves[..] {
+            [best, second, ..] => best.value.lower > second.value.upper,
+            _ => true,
+        }
+    }
+}
+
+impl<M: fmt::Display> fmt::Display for Report<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "move  playouts  {:<23}  {:<23}  {:<23}  value", "wins", "draws", "losses")?;
+        for report in &self.moves {
+            writeln!(
+                f, "{:>4}  {:>8}  {}  {}  {}  {}",
+                report.move_, report.outcomes.playouts(), report.wins, report.draws, report.losses, report.value,
+            )?;
+        }
+        if !self.is_separated() {
+            writeln!(f, "the best move is not statistically separated from the second best move")?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wilson_intervals_contain_the_estimate() {
+        let rate = Rate::wilson(0., 10);
+        assert_eq!(rate.estimate, 0.);
+        assert_eq!(rate.lower, 0.);
+        assert!(rate.upper > 0.2 && rate.upper < 0.35);
+        let rate = Rate::wilson(50., 100);
+        assert!((rate.lower - 0.404).abs() < 1e-3 && (rate.upper - 0.596).abs() < 1e-3);
+        let wide = Rate::wilson(5., 10);
+        assert!(wide.lower < rate.lower && wide.upper > rate.upper);
+    }
+
+    #[test]
+    fn weights_decide_the_value_of_draws() {
+        let outcomes = Outcomes { wins: 2, draws: 4, losses: 2 };
+        assert_eq!(OutcomeWeights::default().value(&outcomes), 0.625);
+        assert_eq!(OutcomeWeights::new(1., 0.5, 0.).value(&outcomes), 0.5);
+        assert_eq!(OutcomeWeights::new(1., 0., 0.).value(&outcomes), 0.25);
+    }
+
+    #[test]
+    fn close_moves_are_not_separated() {
+        let weights = OutcomeWeights::default();
+        let report = |first, second| Report {
+            moves: vec![
+                MoveReport::new(0, first, &weights),
+                MoveReport::new(1, second, &weights),
+            ],
+        };
+        assert!(!report(Outcomes { wins: 6, draws: 0, losses: 4 }, Outcomes { wins: 5, draws: 0, losses: 5 }).is_separated());
+        assert!(report(Outcomes { wins: 600, draws: 0, losses: 400 }, Outcomes { wins: 400, draws: 0, losses: 600 }).is_separated());
+    }
+}
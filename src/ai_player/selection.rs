@@ -0,0 +1,158 @@
+//! Selection policies for the tree search.
+//!
+//! Once all children of a node have been expanded, the tree search descends into the child
+//! with the highest score according to the `SelectionPolicy`. All statistics are given from
+//! the point of view of the player to move at the parent.
+
+use rand::Rng;
+use rand::distributions::{Beta, Distribution};
+
+use super::SearchConfig;
+
+
+/// Statistics of a child node, seen by the player choosing between the children.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChildStatistics {
+    pub visits: u64,
+    /// The expected numbers of results, which may be fractional.
+    pub wins: f64,
+    pub draws: f64,
+    pub losses: f64,
+    /// The normalised prior of the move leading to this child.
+    pub prior: f64,
+}
+
+impl ChildStatistics {
+    /// The mean result, counting draws as half a win.
+    pub fn mean(&self) -> f64 {
+        (self.wins + self.draws / 2.) / self.visits as f64
+    }
+
+    /// The mean of the squared results, used to estimate their variance.
+    fn mean_of_squares(&self) -> f64 {
+        (self.wins + self.draws / 4.) / self.visits as f64
+    }
+
+    /// The progressive bias term of the `Prior`, which fades as the child is visited.
+    fn progressive_bias(&self, config: &SearchConfig) -> f64 {
+        config.prior_weight * self.prior / (self.visits + 1) as f64
+    }
+}
+
+
+pub trait SelectionPolicy: Send + Sync {
+    /// The score of a child with at least one visit. The child with the highest score is
+    /// selected.
+    fn score<R: Rng>(
+        &self,
+        config: &SearchConfig,
+        parent_visits: u64,
+        child: &ChildStatistics,
+        rng: &mut R,
+    ) -> f64;
+}
+
+
+/// UCB1 with `config.exploration` as the exploration weight, plus progressive bias.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Ucb1;
+
+impl SelectionPolicy for Ucb1 {
+    fn score<R: Rng>(&self, config: &SearchConfig, parent_visits: u64, child: &ChildStatistics, _rng: &mut R) -> f64 {
+        let exploration = (config.exploration * (parent_visits as f64).ln() / child.visits as f64).sqrt();
+        child.mean() + exploration + child.progressive_bias(config)
+    }
+}
+
+
+/// UCB1-Tuned (Auer, Cesa-Bianchi and Fischer, 2002), which shrinks the exploration term for
+/// children whose results vary little, plus progressive bias.
+///
+/// The exploration term is scaled so that it equals the one of `Ucb1` for the maximal
+/// variance of `1/4`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Ucb1Tuned;
+
+impl SelectionPolicy for Ucb1Tuned {
+    fn score<R: Rng>(&self, config: &SearchConfig, parent_visits: u64, child: &ChildStatistics, _rng: &mut R) -> f64 {
+        let log_parent_visits = (parent_visits as f64).ln();
+        let visits = child.visits as f64;
+        let mean = child.mean();
+        let variance_bound = child.mean_of_squares() - mean * mean + (2. * log_parent_visits / visits).sqrt();
+        let exploration = (4. * config.exploration * log_parent_visits / visits * variance_bound.min(0.25)).sqrt();
+        mean + exploration + child.progressive_bias(config)
+    }
+}
+
+
+/// Thompson sampling: Scores are drawn from the Beta posterior of each child’s result, with
+/// draws counting half for both players.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Thompson;
+
+impl SelectionPolicy for Thompson {
+    fn score<R: Rng>(&self, _config: &SearchConfig, _parent_visits: u64, child: &ChildStatistics, rng: &mut R) -> f64 {
+        let draws = child.draws / 2.;
+        Beta::new(1. + child.wins + draws, 1. + child.losses + draws).sample(rng)
+    }
+}
+
+
+/// PUCT as used by AlphaZero: the exploration term is weighted by the prior, with
+/// `config.exploration` as `c_puct`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Puct;
+
+impl SelectionPolicy for Puct {
+    fn score<R: Rng>(&self, config: &SearchConfig, parent_visits: u64, child: &ChildStatistics, _rng: &mut R) -> f64 {
+        child.mean()
+            + config.exploration * child.prior * (parent_visits as f64).sqrt() / (child.visits + 1) as f64
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_player::new_rng;
+
+    /// The mean and the highest of many scores of `child`, so that Thompson sampling can be
+    /// compared with the deterministic policies.
+    fn scores<S: SelectionPolicy>(policy: &S, child: &ChildStatistics) -> (f64, f64) {
+        let config = SearchConfig::default();
+        let mut rng = new_rng();
+        let scores: Vec<f64> = (0..1_000).map(|_| policy.score(&config, 1_100, child, &mut rng)).collect();
+        (scores.iter().sum::<f64>() / scores.len() as f64, scores.iter().cloned().fold(f64::MIN, f64::max))
+    }
+
+    fn prefers_good_and_uncertain_children<S: SelectionPolicy>(policy: S) {
+        let child = |visits: u64, wins: f64| {
+            ChildStatistics { visits, wins, draws: 0., losses: visits as f64 - wins, prior: 1. / 7. }
+        };
+        // Of equally visited children, the one with the better results scores higher.
+        assert!(scores(&policy, &child(100, 60.)).0 > scores(&policy, &child(100, 40.)).0);
+        // Of children with the same results, the less visited one can score higher.
+        assert!(scores(&policy, &child(10, 5.)).1 > scores(&policy, &child(1_000, 500.)).1);
+    }
+
+    #[test]
+    fn all_policies_prefer_good_and_uncertain_children() {
+        prefers_good_and_uncertain_children(Ucb1);
+        prefers_good_and_uncertain_children(Ucb1Tuned);
+        prefers_good_and_uncertain_children(Thompson);
+        prefers_good_and_uncertain_children(Puct);
+    }
+
+    #[test]
+    fn tuned_exploration_shrinks_with_low_variance() {
+        let config = SearchConfig::default();
+        let certain = ChildStatistics { visits: 10_000, wins: 10_000., draws: 0., losses: 0., prior: 0. };
+        let uncertain = ChildStatistics { visits: 10_000, wins: 5_000., draws: 0., losses: 5_000., prior: 0. };
+        let exploration = |child: &ChildStatistics| {
+            Ucb1Tuned.score(&config, 1_000_000, child, &mut new_rng()) - child.mean()
+        };
+        assert!(exploration(&certain) < exploration(&uncertain));
+        let ucb1_exploration = Ucb1.score(&config, 1_000_000, &uncertain, &mut new_rng()) - uncertain.mean();
+        assert!((exploration(&uncertain) - ucb1_exploration).abs() < 1e-9);
+    }
+}
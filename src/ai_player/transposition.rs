@@ -0,0 +1,310 @@
+//! Monte Carlo tree search on a graph of positions.
+//!
+//! Many move orders lead to the same position, which `SearchTree` stores (and evaluates) once
+//! per move order. `SearchGraph` instead stores every position once in a table keyed by
+//! `PositionKey::position_key`, so that all playouts through a position contribute to the same
+//! statistics.
+//!
+//! The statistics of a node are shared between all its parents, but the number of times each
+//! edge was taken is stored in the edge itself. Selection uses the value of the child node
+//! together with the visits of the edge for the exploration term (UCT2 in Childs, Brodeur and
+//! Kocsis, “Transpositions and Move Groups in Monte Carlo Tree Search”, 2008). A playout only
+//! updates the nodes on the path it actually took, so no node is updated twice per playout and
+//! the parents of a node do not need to be known.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::marker::PhantomData;
+
+use rand::Rng;
+
+use super::{choose, new_rng, random_playout, ucb, MoveStatistics, SearchConfig, STOP_CHECK_INTERVAL};
+use crate::{tactics, Analysis, Game, Player, PlayerTrait, PositionKey, SearchInfo, Thought};
+
+
+#[derive(Clone)]
+pub struct GraphSearchPlayer<G: PositionKey> {
+    config: SearchConfig,
+    _game: PhantomData<G>,
+}
+
+impl<G: PositionKey> Default for GraphSearchPlayer<G> {
+    fn default() -> GraphSearchPlayer<G> {
+        Self::with_config(SearchConfig::default())
+    }
+}
+
+impl<G: PositionKey> GraphSearchPlayer<G> {
+    pub fn new() -> GraphSearchPlayer<G> {
+        Self::default()
+    }
+
+    pub fn with_config(config: SearchConfig) -> GraphSearchPlayer<G> {
+        GraphSearchPlayer {
+            config,
+            _game: PhantomData,
+        }
+    }
+}
+
+impl<G: PositionKey> PlayerTrait for GraphSearchPlayer<G> {
+    type Game = G;
+
+    fn make_move(&self, game: &G) -> G::Move {
+        self.analyse(game).0
+    }
+
+    fn analyse(&self, game: &G) -> Analysis<G::Move> {
+        self.think(game, &|| false).0
+    }
+
+    fn think(&self, game: &G, stop: &dyn Fn() -> bool) -> Thought<G::Move> {
+        let (move_, statistics) = tactics::safety_net(game, |game| {
+            let mut graph = SearchGraph::with_config(game, self.config);
+            graph.search_until(self.config.simulations, stop);
+            log::debug!("{} positions after {} playouts", graph.len(), graph.visits());
+            (graph.best_move(), graph.move_statistics())
+        });
+        let info = SearchInfo::from_statistics(move_, &statistics);
+        ((move_, statistics), info)
+    }
+}
+
+
+pub struct SearchGraph<G: PositionKey> {
+    game: G,
+    root: u64,
+    config: SearchConfig,
+    nodes: HashMap<u64, Node<G>>,
+}
+
+impl<G: PositionKey> SearchGraph<G> {
+    pub fn new(game: &G) -> SearchGraph<G> {
+        Self::with_config(game, SearchConfig::default())
+    }
+
+    pub fn with_config(game: &G, config: SearchConfig) -> SearchGraph<G> {
+        let root = game.position_key();
+        let mut nodes = HashMap::new();
+        nodes.insert(root, Node::new(game));
+        SearchGraph {
+            game: game.clone(),
+            root,
+            config,
+            nodes,
+        }
+    }
+
+    /// The number of distinct positions in the graph.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn visits(&self) -> u64 {
+        self.nodes[&self.root].visits
+    }
+
+    pub fn search(&mut self, simulations: usize) {
+        self.search_until(simulations, &|| false);
+    }
+
+    /// Runs up to `simulations` more playouts, but stops early once `stop` returns `true`.
+    /// `stop` is only called every `STOP_CHECK_INTERVAL` playouts.
+    pub fn search_until(&mut self, simulations: usize, stop: &dyn Fn() -> bool) {
+        let mut rng = new_rng();
+        for simulation in 0..simulations {
+            if simulation % STOP_CHECK_INTERVAL == 0 && stop() {
+                break;
+            }
+            self.step(&mut rng);
+        }
+    }
+
+    /// Statistics of all moves at the root, most visited first. The visits are those of the
+    /// edges, the values those of the positions the moves lead to, which playouts through
+    /// transpositions contribute to as well.
+    pub fn move_statistics(&self) -> Vec<MoveStatistics<G::Move>> {
+        let mut statistics: Vec<_> = self.nodes[&self.root].edges.iter()
+            .map(|edge| match edge.child {
+                Some(child) => {
+                    let child = &self.nodes[&child];
+                    MoveStatistics {
+                        move_: edge.move_,
+                        visits: edge.visits,
+                        value: (child.wins as f64 + child.draws as f64 / 2.) / child.visits as f64,
+                    }
+                }
+                None => MoveStatistics { move_: edge.move_, visits: 0, value: 0.5 },
+            })
+            .collect();
+        statistics.sort_by_key(|statistics| std::cmp::Reverse(statistics.visits));
+        statistics
+    }
+
+    /// The move whose edge was taken most often from the root.
+    pub fn best_move(&self) -> G::Move {
+        self.nodes[&self.root].edges.iter()
+            .max_by_key(|edge| edge.visits)
+            .expect("Could not find valid move")
+            .move_
+    }
+
+    fn step(&mut self, rng: &mut impl Rng) {
+        let me = self.game.current_player();
+        let mut game = self.game.clone();
+        let mut key = self.root;
+        let mut path = vec![];
+
+        let result = loop {
+            if game.has_ended() {
+                break game.winner();
+            }
+
+            let node = &self.nodes[&key];
+            let expandable_edges: Vec<_> = node.edges.iter()
+                .enumerate()
+                .filter(|(_, edge)| edge.child.is_none())
+                .map(|(index, _)| index)
+                .collect();
+            let index = if expandable_edges.is_empty() {
+                node.best_edge(me, &game, &self.nodes, &self.config)
+            }
+            else {
+                *choose(rng, &expandable_edges)
+            };
+
+            let move_ = node.edges[index].move_;
+            game.play(move_).unwrap_or_else(|err| panic!("tried to play invalid move: {:?}", err));
+            let child = game.position_key();
+            path.push((key, index));
+            self.nodes.get_mut(&key).expect("nodes on the path exist").edges[index].child = Some(child);
+            key = child;
+
+            if let Entry::Vacant(entry) = self.nodes.entry(child) {
+                entry.insert(Node::new(&game));
+                break random_playout(rng, game);
+            }
+        };
+
+        self.nodes.get_mut(&self.root).expect("root exists").visited(me, result);
+        for (parent, index) in path {
+            let parent = self.nodes.get_mut(&parent).expect("nodes on the path exist");
+            let edge = &mut parent.edges[index];
+            edge.visits += 1;
+            let child = edge.child.expect("edges on the path have been expanded");
+            self.nodes.get_mut(&child).expect("children on the path exist").visited(me, result);
+        }
+    }
+}
+
+
+struct Edge<G: Game> {
+    move_: G::Move,
+    child: Option<u64>,
+    visits: u64,
+}
+
+struct Node<G: Game> {
+    edges: Vec<Edge<G>>,
+    visits: u64,
+    wins: u64,
+    draws: u64,
+}
+
+impl<G: Game> Node<G> {
+    fn new(game: &G) -> Node<G> {
+        Node {
+            edges: game.valid_moves().into_iter()
+                .map(|move_| Edge { move_, child: None, visits: 0 })
+                .collect(),
+            visits: 0,
+            wins: 0,
+            draws: 0,
+        }
+    }
+
+    fn best_edge(
+        &self,
+        me: Player,
+        game: &G,
+        nodes: &HashMap<u64, Node<G>>,
+        config: &SearchConfig,
+    ) -> usize {
+        let parent_visits: u64 = self.edges.iter().map(|edge| edge.visits).sum();
+        self.edges.iter()
+            .enumerate()
+            .max_by_key(|(_, edge)| {
+                let child = &nodes[&edge.child.expect("node is fully expanded")];
+                // Draws count as half a win, as in `SearchTree`.
+                let wins = child.wins as f64 + child.draws as f64 / 2.;
+                let value = if game.current_player() == me {
+                    wins / child.visits as f64
+                }
+                else {
+                    1. - wins / child.visits as f64
+                };
+                let exploration = ucb(parent_visits as f64, 0., edge.visits as f64, config.exploration);
+                ((value + exploration) * 1e15) as u64
+            })
+            .expect("node does not have any edges")
+            .0
+    }
+
+    fn visited(&mut self, me: Player, winner: Option<Player>) {
+        self.visits += 1;
+        if winner.is_none() {
+            self.draws += 1;
+        }
+        else if winner == Some(me) {
+            self.wins += 1;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitState, ConnectFour, VecState};
+
+    #[test]
+    fn transpositions_have_the_same_key() {
+        assert_eq!(ConnectFour::<BitState>::from_moves(7, 6, &[0, 1, 2]).position_key(), ConnectFour::<BitState>::from_moves(7, 6, &[2, 1, 0]).position_key());
+        assert_ne!(ConnectFour::<BitState>::from_moves(7, 6, &[0, 1, 2]).position_key(), ConnectFour::<BitState>::from_moves(7, 6, &[2, 0, 1]).position_key());
+        assert_ne!(ConnectFour::<BitState>::from_moves(7, 6, &[]).position_key(), ConnectFour::<BitState>::from_moves(7, 6, &[3]).position_key());
+        assert_eq!(ConnectFour::<VecState>::from_moves(7, 6, &[0, 1, 2]).position_key(), ConnectFour::<VecState>::from_moves(7, 6, &[2, 1, 0]).position_key());
+        assert_ne!(ConnectFour::<VecState>::from_moves(7, 6, &[0, 1, 2]).position_key(), ConnectFour::<VecState>::from_moves(7, 6, &[2, 0, 1]).position_key());
+    }
+
+    #[test]
+    fn graph_shares_nodes_between_transpositions() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[]);
+        let mut graph = SearchGraph::new(&game);
+        graph.search(5_000);
+        assert_eq!(graph.visits(), 5_000);
+
+        let mut incoming = HashMap::<u64, Vec<u64>>::new();
+        for node in graph.nodes.values() {
+            for edge in node.edges.iter() {
+                if let Some(child) = edge.child {
+                    incoming.entry(child).or_default().push(edge.visits);
+                }
+            }
+        }
+        for (key, node) in graph.nodes.iter().filter(|&(&key, _)| key != graph.root) {
+            assert_eq!(node.visits, incoming[key].iter().sum::<u64>());
+        }
+        assert!(incoming.values().any(|edges| edges.len() > 1));
+    }
+
+    #[test]
+    fn takes_immediate_win() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[0, 6, 0, 6, 0, 6]);
+        let player = GraphSearchPlayer::with_config(SearchConfig { simulations: 2_000, ..Default::default() });
+        assert_eq!(player.make_move(&game), 0);
+    }
+}
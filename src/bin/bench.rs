@@ -16,7 +16,7 @@ fn as_fractional_secs(duration: Duration) -> f64 {
 fn main() {
     let columns = 7;
     let game = ConnectFour::<BitState>::new(columns, 6).unwrap();
-    let benchmark_player = MonteCarloPlayer::new();
+    let benchmark_player = MonteCarloPlayer::with_budget(SIMULATIONS, Allocation::Uniform);
     let now = Instant::now();
     benchmark_player.make_move(&game);
     let seconds = as_fractional_secs(now.elapsed());
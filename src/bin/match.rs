@@ -0,0 +1,267 @@
+//! Plays a match between two engines and reports their Elo difference, to measure whether a
+//! change made an engine stronger.
+//!
+//! The engines alternate colours: every opening is played twice, once with each engine
+//! moving first. Games are played in parallel unless the `noparallel` feature is enabled, but
+//! are always written in order, one line per game: its number, the engines playing X and O,
+//! the result from X’s point of view (`1-0`, `0-1` or `1/2-1/2`) and all moves including the
+//! opening, separated by spaces.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(not(feature = "noparallel"))]
+use rayon::prelude::*;
+
+use mcc4::*;
+use mcc4::elo::Results;
+
+const USAGE: &str = "usage: match [--games N] [--engines ENGINE,ENGINE] [--simulations N] \
+    [--openings PLIES|FILE] [--output FILE]
+engines: mcts[:POLICY][:SIMULATIONS], graph[:SIMULATIONS], montecarlo[:SIMULATIONS], solver[:SECONDS], network:FILE
+or a difficulty (beginner ... perfect), see `mcc4::engine_spec`
+openings: all balanced openings with PLIES moves, or one opening per line of FILE as columns
+separated by spaces";
+
+const COLUMNS: usize = 7;
+const ROWS: usize = 6;
+
+/// Games are played in chunks of this size, so that finished games can be written while
+/// memory use stays bounded.
+const CHUNK_SIZE: u32 = 64;
+
+/// The playouts of the search that judges whether an opening is balanced.
+const OPENING_SIMULATIONS: usize = 5_000;
+/// Openings are balanced if the search expects a score within this distance of 0.5 for the
+/// player to move.
+const OPENING_BALANCE: f64 = 0.1;
+
+/// The thinking time per move of solvers without their own.
+const TIME_LIMIT: Duration = Duration::from_secs(1);
+
+struct Options {
+    games: u32,
+    engines: [String; 2],
+    simulations: usize,
+    /// A number of plies or a file.
+    openings: Option<String>,
+    output: String,
+}
+
+fn parse_value<T: FromStr>(arguments: &mut impl Iterator<Item = String>, name: &str) -> Result<T, String> {
+    let value = arguments.next().ok_or_else(|| format!("missing value for `{}`", name))?;
+    value.parse().map_err(|_| format!("invalid value `{}` for `{}`", value, name))
+}
+
+fn parse_arguments() -> Result<Options, String> {
+    let mut options = Options {
+        games: 100,
+        engines: ["mcts".to_string(), "montecarlo".to_string()],
+        simulations: 10_000,
+        openings: None,
+        output: "match.txt".to_string(),
+    };
+    let mut arguments = env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--games" | "-n" => options.games = parse_value(&mut arguments, &argument)?,
+            "--engines" | "-e" => {
+                let engines: String = parse_value(&mut arguments, &argument)?;
+                match engines.split(',').collect::<Vec<_>>().as_slice() {
+                    [first, second] => options.engines = [first.to_string(), second.to_string()],
+                    _ => return Err("expected two engines".to_string()),
+                }
+            }
+            "--simulations" | "-s" => options.simulations = parse_value(&mut arguments, &argument)?,
+            "--openings" => options.openings = Some(parse_value(&mut arguments, &argument)?),
+            "--output" | "-o" => options.output = parse_value(&mut arguments, &argument)?,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unexpected argument `{}`", argument)),
+        }
+    }
+    Ok(options)
+}
+
+
+fn new_game() -> ConnectFour<BitState> {
+    ConnectFour::new(COLUMNS, ROWS).unwrap()
+}
+
+/// Whether the game can go on after `opening`.
+fn is_playable(opening: &[usize]) -> bool {
+    let mut game = new_game();
+    opening.iter().all(|&move_| !game.has_ended() && game.play(move_).is_ok()) && !game.has_ended()
+}
+
+/// Every playable opening with `plies` moves, without mirror images, after which a short
+/// search considers neither side clearly better.
+fn balanced_openings(plies: usize) -> Vec<Vec<usize>> {
+    let mut openings = vec![vec![]];
+    for _ in 0..plies {
+        openings = openings.into_iter()
+            .flat_map(|opening: Vec<usize>| (0..COLUMNS).map(move |move_| [&opening[..], &[move_]].concat()))
+            .filter(|opening| is_playable(opening))
+            .collect();
+    }
+    let judge = TreeSearchPlayer::with_config(SearchConfig { simulations: OPENING_SIMULATIONS, ..SearchConfig::default() });
+    openings.into_iter()
+        .filter(|opening| {
+            let mirrored: Vec<_> = opening.iter().map(|move_| COLUMNS - 1 - move_).collect();
+            *opening <= mirrored
+        })
+        .filter(|opening| {
+            let game = new_game().iter(vec![]).replay(opening).unwrap().game().clone();
+            let (_, info) = judge.think(&game, &|| false);
+            info.value.is_some_and(|value| (value - 0.5).abs() <= OPENING_BALANCE)
+        })
+        .collect()
+}
+
+/// The openings in `path`, one per line as columns separated by whitespace. Empty lines are
+/// skipped.
+fn load_openings(path: &str) -> Result<Vec<Vec<usize>>, String> {
+    let text = fs::read_to_string(path).map_err(|error| format!("cannot read `{}`: {}", path, error))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let opening = line.split_whitespace()
+                .map(|move_| move_.parse().map_err(|_| format!("invalid move `{}` in `{}`", move_, path)))
+                .collect::<Result<Vec<usize>, _>>()?;
+            if is_playable(&opening) { Ok(opening) } else { Err(format!("invalid opening `{}` in `{}`", line, path)) }
+        })
+        .collect()
+}
+
+
+/// A finished game.
+struct Record {
+    index: u32,
+    /// The engine that played X.
+    first: usize,
+    winner: Option<Player>,
+    moves: Vec<usize>,
+}
+
+/// Players are created per game, because they are not necessarily `Sync`.
+fn play(index: u32, engines: &[EngineSpec; 2], openings: &[Vec<usize>], simulations: usize) -> Record {
+    let opening = match openings {
+        [] => &[][..],
+        openings => &openings[(index / 2) as usize % openings.len()],
+    };
+    let first = (index % 2) as usize;
+    let player = |engine: &EngineSpec| engine.player(&new_game(), simulations, TIME_LIMIT).expect("checked before playing");
+    let players = vec![player(&engines[first]), player(&engines[1 - first])];
+    let mut moves = new_game().iter(players).replay(opening).expect("openings are playable");
+    while moves.next().is_some() {}
+    Record { index, first, winner: moves.game().winner(), moves: moves.history().to_vec() }
+}
+
+#[cfg(not(feature = "noparallel"))]
+fn play_games(games: std::ops::Range<u32>, engines: &[EngineSpec; 2], openings: &[Vec<usize>], simulations: usize) -> Vec<Record> {
+    games.into_par_iter().map(|index| play(index, engines, openings, simulations)).collect()
+}
+
+#[cfg(feature = "noparallel")]
+fn play_games(games: std::ops::Range<u32>, engines: &[EngineSpec; 2], openings: &[Vec<usize>], simulations: usize) -> Vec<Record> {
+    games.map(|index| play(index, engines, openings, simulations)).collect()
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    let invalid_input = |error| io::Error::new(io::ErrorKind::InvalidInput, error);
+    // Engines are checked once here, for example networks trained for another board size.
+    let parse = |name: &str| {
+        name.parse::<EngineSpec>()
+            .and_then(|engine| engine.player(&new_game(), options.simulations, TIME_LIMIT).map(|_| engine))
+            .map_err(invalid_input)
+    };
+    let engines = [parse(&options.engines[0])?, parse(&options.engines[1])?];
+    let openings = match &options.openings {
+        None => vec![],
+        Some(openings) => match openings.parse() {
+            Ok(plies) => balanced_openings(plies),
+            Err(_) => load_openings(openings).map_err(invalid_input)?,
+        },
+    };
+    if options.openings.is_some() {
+        if openings.is_empty() {
+            return Err(invalid_input("no openings to play".to_string()));
+        }
+        eprintln!("{} openings", openings.len());
+    }
+    let mut writer = BufWriter::new(File::create(&options.output)?);
+
+    // From the point of view of the first engine.
+    let mut results = Results::default();
+    for start in (0..options.games).step_by(CHUNK_SIZE as usize) {
+        let end = (start + CHUNK_SIZE).min(options.games);
+        for record in play_games(start..end, &engines, &openings, options.simulations) {
+            let result = match record.winner {
+                Some(Player(1)) => "1-0",
+                Some(_) => "0-1",
+                None => "1/2-1/2",
+            };
+            match record.winner {
+                None => results.draws += 1,
+                Some(Player(winner)) if usize::from(winner - 1) == record.first => results.wins += 1,
+                Some(_) => results.losses += 1,
+            }
+            let moves: Vec<_> = record.moves.iter().map(|move_| move_.to_string()).collect();
+            writeln!(
+                writer,
+                "{} {} {} {} {}",
+                record.index + 1,
+                options.engines[record.first],
+                options.engines[1 - record.first],
+                result,
+                moves.join(" "),
+            )?;
+        }
+        writer.flush()?;
+        eprintln!(
+            "{}/{} games, {} {}/{}/{} (won/drawn/lost) against {}",
+            end,
+            options.games,
+            options.engines[0],
+            results.wins,
+            results.draws,
+            results.losses,
+            options.engines[1],
+        );
+    }
+
+    if let Some(score) = results.score() {
+        println!(
+            "{} against {}: {} wins, {} draws, {} losses, score {:.1} %",
+            options.engines[0],
+            options.engines[1],
+            results.wins,
+            results.draws,
+            results.losses,
+            100. * score,
+        );
+        match results.elo_difference() {
+            Some((difference, margin)) => println!("Elo difference: {:+.1} ± {:.1}", difference, margin),
+            None => println!("Elo difference: unknown, one engine won every game"),
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    env_logger::init();
+    let options = parse_arguments().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(&options) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,180 @@
+//! Plays games between two engines and writes every position to a dataset file.
+//!
+//! The engines swap seats after every game. Games are played in parallel unless the
+//! `noparallel` feature is enabled, but are always written in order.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[cfg(not(feature = "noparallel"))]
+use rayon::prelude::*;
+
+use mcc4::*;
+use mcc4::dataset::{self, Record};
+
+const USAGE: &str = "usage: selfplay [--games N] [--engines ENGINE,ENGINE] [--simulations N] \
+    [--format binary|csv] [--output FILE]
+engines: mcts[:POLICY][:SIMULATIONS], graph[:SIMULATIONS], montecarlo[:SIMULATIONS], solver[:SECONDS], network:FILE
+or a difficulty (beginner ... perfect), see `mcc4::engine_spec`";
+
+const COLUMNS: usize = 7;
+const ROWS: usize = 6;
+
+/// Games are played in chunks of this size, so that finished games can be written while
+/// memory use stays bounded.
+const CHUNK_SIZE: u32 = 64;
+
+/// The thinking time per move of solvers without their own.
+const TIME_LIMIT: Duration = Duration::from_secs(1);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Binary,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "binary" => Ok(Format::Binary),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format `{}`, expected binary or csv", s)),
+        }
+    }
+}
+
+struct Options {
+    games: u32,
+    engines: [String; 2],
+    simulations: usize,
+    format: Format,
+    output: String,
+}
+
+fn parse_value<T: FromStr>(arguments: &mut impl Iterator<Item = String>, name: &str) -> Result<T, String> {
+    let value = arguments.next().ok_or_else(|| format!("missing value for `{}`", name))?;
+    value.parse().map_err(|_| format!("invalid value `{}` for `{}`", value, name))
+}
+
+fn parse_arguments() -> Result<Options, String> {
+    let mut options = Options {
+        games: 100,
+        engines: ["mcts".to_string(), "mcts".to_string()],
+        simulations: 10_000,
+        format: Format::Binary,
+        output: "selfplay.bin".to_string(),
+    };
+    let mut arguments = env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--games" | "-n" => options.games = parse_value(&mut arguments, &argument)?,
+            "--engines" | "-e" => {
+                let engines: String = parse_value(&mut arguments, &argument)?;
+                let engines: Vec<_> = engines.split(',').map(str::to_string).collect();
+                match engines.as_slice() {
+                    [engine] => options.engines = [engine.clone(), engine.clone()],
+                    [first, second] => options.engines = [first.clone(), second.clone()],
+                    _ => return Err("expected one or two engines".to_string()),
+                }
+            }
+            "--simulations" | "-s" => options.simulations = parse_value(&mut arguments, &argument)?,
+            "--format" | "-f" => {
+                let format: String = parse_value(&mut arguments, &argument)?;
+                options.format = format.parse()?;
+            }
+            "--output" | "-o" => options.output = parse_value(&mut arguments, &argument)?,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unexpected argument `{}`", argument)),
+        }
+    }
+    Ok(options)
+}
+
+/// Players are created per game, because they are not necessarily `Sync`.
+fn play(index: u32, engines: &[EngineSpec; 2], simulations: usize) -> Vec<Record> {
+    let game = ConnectFour::<BitState>::new(COLUMNS, ROWS).unwrap();
+    let first = (index % 2) as usize;
+    let player = |engine: &EngineSpec| engine.player(&game, simulations, TIME_LIMIT).expect("checked before playing");
+    let first_player = player(&engines[first]);
+    let second_player = player(&engines[1 - first]);
+    dataset::record_game(index, game.clone(), &*first_player, &*second_player)
+}
+
+#[cfg(not(feature = "noparallel"))]
+fn play_games(games: std::ops::Range<u32>, engines: &[EngineSpec; 2], simulations: usize) -> Vec<Vec<Record>> {
+    games.into_par_iter().map(|index| play(index, engines, simulations)).collect()
+}
+
+#[cfg(feature = "noparallel")]
+fn play_games(games: std::ops::Range<u32>, engines: &[EngineSpec; 2], simulations: usize) -> Vec<Vec<Record>> {
+    games.map(|index| play(index, engines, simulations)).collect()
+}
+
+fn run(options: &Options) -> io::Result<()> {
+    // Engines are checked once here, for example networks trained for another board size.
+    let game = ConnectFour::<BitState>::new(COLUMNS, ROWS).unwrap();
+    let parse = |name: &str| {
+        name.parse::<EngineSpec>()
+            .and_then(|engine| engine.player(&game, options.simulations, TIME_LIMIT).map(|_| engine))
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+    };
+    let engines = [parse(&options.engines[0])?, parse(&options.engines[1])?];
+    let mut writer = BufWriter::new(File::create(&options.output)?);
+    match options.format {
+        Format::Binary => dataset::write_binary_header(&mut writer, COLUMNS, ROWS)?,
+        Format::Csv => writeln!(writer, "{}", dataset::CSV_HEADER)?,
+    }
+
+    let mut positions = 0;
+    let mut results = [0; 3];
+    for start in (0..options.games).step_by(CHUNK_SIZE as usize) {
+        let end = (start + CHUNK_SIZE).min(options.games);
+        for records in play_games(start..end, &engines, options.simulations) {
+            // Count results from the point of view of the first engine.
+            let first_record = &records[0];
+            let result = if first_record.game % 2 == 0 { first_record.result } else { -first_record.result };
+            results[(1 - result) as usize] += 1;
+            positions += records.len();
+            for record in &records {
+                match options.format {
+                    Format::Binary => record.write_binary(&mut writer)?,
+                    Format::Csv => record.write_csv(&mut writer)?,
+                }
+            }
+        }
+        writer.flush()?;
+        eprintln!(
+            "{}/{} games, {} positions, {} {}/{}/{} (won/drawn/lost) against {}",
+            end,
+            options.games,
+            positions,
+            options.engines[0],
+            results[0],
+            results[1],
+            results[2],
+            options.engines[1],
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    env_logger::init();
+    let options = parse_arguments().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(&options) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
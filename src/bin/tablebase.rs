@@ -0,0 +1,62 @@
+//! Builds the tablebase of a small board and writes it to a file. The layers are written as
+//! they are solved, see `Tablebase::build_into`.
+
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process;
+use std::time::Instant;
+
+use mcc4::*;
+use mcc4::tablebase::Value;
+
+const USAGE: &str = "usage: tablebase COLUMNS ROWS OUTPUT";
+
+fn main() {
+    env_logger::init();
+    let arguments: Vec<_> = env::args().skip(1).collect();
+    let (columns, rows, output) = match arguments.as_slice() {
+        [columns, rows, output] => match (columns.parse(), rows.parse()) {
+            (Ok(columns), Ok(rows)) => (columns, rows, output),
+            _ => {
+                eprintln!("invalid board size\n{}", USAGE);
+                process::exit(2);
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if !Tablebase::supports(columns, rows) {
+        eprintln!("unsupported board size {}×{}", columns, rows);
+        process::exit(2);
+    }
+
+    let now = Instant::now();
+    let built = File::create(output)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            let built = Tablebase::build_into(columns, rows, &mut writer)?;
+            writer.flush()?;
+            Ok(built)
+        });
+    let (entry, positions) = built.unwrap_or_else(|error| {
+        eprintln!("cannot write `{}`: {}", output, error);
+        process::exit(1);
+    });
+    let result = match entry.value {
+        Value::Win => "first player wins",
+        Value::Draw => "draw",
+        Value::Loss => "second player wins",
+    };
+    println!(
+        "{}×{}: {} after {} plies, {} positions, built in {:.1} seconds",
+        columns,
+        rows,
+        result,
+        entry.distance,
+        positions,
+        now.elapsed().as_secs_f64(),
+    );
+}
@@ -0,0 +1,160 @@
+//! Trains a `Network` by self-play and writes a checkpoint after every iteration.
+//!
+//! Every iteration plays a number of self-play games with the current network, adds their
+//! positions and mirror images to a replay buffer of the most recent samples and trains the
+//! network on random minibatches drawn from the buffer.
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use rand::{thread_rng, seq::SliceRandom};
+
+use mcc4::*;
+use mcc4::ai_player::{self_play, Sample, SelfPlayConfig};
+
+const USAGE: &str = "usage: train [--iterations N] [--games N] [--simulations N] [--batches N] \
+    [--batch-size N] [--learning-rate X] [--checkpoints DIRECTORY] [--resume FILE]";
+
+const COLUMNS: usize = 7;
+const ROWS: usize = 6;
+const HIDDEN_LAYERS: [usize; 2] = [128, 64];
+const REPLAY_BUFFER_SIZE: usize = 50_000;
+const WEIGHT_DECAY: f64 = 1e-4;
+
+struct Options {
+    iterations: usize,
+    games: usize,
+    simulations: usize,
+    batches: usize,
+    batch_size: usize,
+    learning_rate: f64,
+    checkpoints: PathBuf,
+    resume: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            iterations: 100,
+            games: 20,
+            simulations: SelfPlayConfig::default().search.simulations,
+            batches: 200,
+            batch_size: 64,
+            learning_rate: 0.01,
+            checkpoints: PathBuf::from("checkpoints"),
+            resume: None,
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(arguments: &mut impl Iterator<Item = String>, name: &str) -> Result<T, String> {
+    let value = arguments.next().ok_or_else(|| format!("missing value for `{}`", name))?;
+    value.parse().map_err(|_| format!("invalid value `{}` for `{}`", value, name))
+}
+
+fn parse_arguments() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut arguments = env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--iterations" => options.iterations = parse_value(&mut arguments, &argument)?,
+            "--games" => options.games = parse_value(&mut arguments, &argument)?,
+            "--simulations" => options.simulations = parse_value(&mut arguments, &argument)?,
+            "--batches" => options.batches = parse_value(&mut arguments, &argument)?,
+            "--batch-size" => options.batch_size = parse_value(&mut arguments, &argument)?,
+            "--learning-rate" => options.learning_rate = parse_value(&mut arguments, &argument)?,
+            "--checkpoints" => options.checkpoints = parse_value(&mut arguments, &argument)?,
+            "--resume" => options.resume = Some(parse_value(&mut arguments, &argument)?),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unexpected argument `{}`", argument)),
+        }
+    }
+    Ok(options)
+}
+
+fn load_network(options: &Options) -> Result<Network, String> {
+    match &options.resume {
+        Some(path) => {
+            let file = File::open(path).map_err(|error| format!("cannot open `{}`: {}", path.display(), error))?;
+            let network = Network::load(&mut BufReader::new(file))
+                .map_err(|error| format!("cannot load `{}`: {:?}", path.display(), error))?;
+            if network.size() != (COLUMNS, ROWS) {
+                return Err(format!("`{}` was trained for another board size", path.display()));
+            }
+            Ok(network)
+        }
+        None => Ok(Network::new(COLUMNS, ROWS, &HIDDEN_LAYERS, &mut thread_rng())),
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let options = parse_arguments().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+    let mut network = load_network(&options).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    if let Err(error) = fs::create_dir_all(&options.checkpoints) {
+        eprintln!("cannot create `{}`: {}", options.checkpoints.display(), error);
+        process::exit(1);
+    }
+
+    let mut config = SelfPlayConfig::default();
+    config.search.simulations = options.simulations;
+    let mut rng = thread_rng();
+    let mut replay_buffer = VecDeque::<Sample>::with_capacity(REPLAY_BUFFER_SIZE);
+
+    for iteration in 1..=options.iterations {
+        let shared_network = Arc::new(network.clone());
+        let mut results = [0; 3];
+        for _ in 0..options.games {
+            let samples = self_play(&shared_network, ConnectFour::<BitState>::new(COLUMNS, ROWS).unwrap(), &config, &mut rng);
+            let first_player_result = samples[0].value.iter().position(|&value| value == 1.).unwrap();
+            results[first_player_result] += 1;
+            for sample in samples {
+                replay_buffer.push_back(sample.mirrored(COLUMNS, ROWS));
+                replay_buffer.push_back(sample);
+            }
+        }
+        while replay_buffer.len() > REPLAY_BUFFER_SIZE {
+            replay_buffer.pop_front();
+        }
+
+        let samples = replay_buffer.make_contiguous();
+        let mut loss = 0.;
+        for _ in 0..options.batches {
+            let batch: Vec<_> = samples.choose_multiple(&mut rng, options.batch_size).cloned().collect();
+            loss += network.loss(&batch);
+            network.train(&batch, options.learning_rate, WEIGHT_DECAY);
+        }
+
+        let path = options.checkpoints.join(format!("network-{:04}.bin", iteration));
+        let saved = File::create(&path).and_then(|file| network.save(&mut BufWriter::new(file)));
+        if let Err(error) = saved {
+            eprintln!("cannot write `{}`: {}", path.display(), error);
+            process::exit(1);
+        }
+        println!(
+            "iteration {}: first player {}/{}/{} (won/drawn/lost), {} samples, loss {:.4}, saved {}",
+            iteration,
+            results[0],
+            results[1],
+            results[2],
+            samples.len(),
+            loss / options.batches.max(1) as f64,
+            path.display(),
+        );
+    }
+}
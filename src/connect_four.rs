@@ -1,6 +1,15 @@
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::prelude::*;
 
-use super::{Game, Player};
+use super::{Game, Notation, Player, PositionKey};
+use super::persist::{Persist, invalid_data};
+use self::render::Renderer;
+
+pub mod eval;
+pub mod render;
 
 #[derive(Debug, Clone)]
 pub struct ConnectFour<S: State> {
@@ -10,6 +19,7 @@ pub struct ConnectFour<S: State> {
 }
 
 impl<S: State> ConnectFour<S> {
+    #[allow(clippy::result_unit_err)]
     pub fn new(columns: usize, rows: usize) -> Result<ConnectFour<S>, ()> {
         Ok(
             ConnectFour {
@@ -20,10 +30,26 @@ impl<S: State> ConnectFour<S> {
         )
     }
 
+    /// A game where `win_length` stones in a line win, if the state supports it.
+    #[allow(clippy::result_unit_err)]
+    pub fn with_win_length(columns: usize, rows: usize, win_length: usize) -> Result<ConnectFour<S>, ()> {
+        Ok(
+            ConnectFour {
+                current_player: Player(1),
+                state: S::with_win_length(columns, rows, win_length)?,
+                winner: None,
+            }
+        )
+    }
+
     pub fn size(&self) -> (usize, usize) {
         self.state.size()
     }
 
+    pub fn win_length(&self) -> usize {
+        self.state.win_length()
+    }
+
     // Not in trait, because it assumes two players.
     pub fn other_player(&self) -> Player {
         let Player(p) = self.current_player();
@@ -31,6 +57,18 @@ impl<S: State> ConnectFour<S> {
     }
 }
 
+#[cfg(test)]
+impl<S: State> ConnectFour<S> {
+    /// A `columns`×`rows` game after `moves`, for setting up test positions.
+    pub(crate) fn from_moves(columns: usize, rows: usize, moves: &[usize]) -> ConnectFour<S> {
+        let mut game = ConnectFour::new(columns, rows).unwrap();
+        for &move_ in moves {
+            game.play(move_).unwrap();
+        }
+        game
+    }
+}
+
 impl<S: State> Game for ConnectFour<S> {
     type State = S;
     type Move = usize;
@@ -69,19 +107,213 @@ impl<S: State> Game for ConnectFour<S> {
         self.current_player
     }
 
+    fn remaining_moves(&self) -> Option<usize> {
+        Some(self.state.empty_cells())
+    }
+
+    fn winning_moves(&self) -> Vec<usize> {
+        if self.winner.is_some() {
+            return vec![];
+        }
+        self.state.winning_columns(self.current_player)
+    }
+
     fn next_player(&mut self) {
         self.current_player = self.other_player();
     }
 }
 
+impl<S: State> PositionKey for ConnectFour<S> {
+    fn position_key(&self) -> u64 {
+        self.state.key()
+    }
+}
+
+/// The win length followed by the rows from top to bottom, separated by `/`, with `x` and `o`
+/// for the stones of the first and the second player and `.` for empty cells, e. g.
+/// `4 ......./......./......./......./......./...xO..`. The last stone played is upper case.
+impl<S: State> Notation for ConnectFour<S> {
+    fn notation(&self) -> String {
+        let (columns, rows) = self.size();
+        let last_move = if self.state.empty_cells() < columns * rows { Some(self.state.last_move()) } else { None };
+        let rows: Vec<String> = (0..rows)
+            .map(|row| (0..columns)
+                .map(|column| {
+                    let stone = match self.state.get(column, row) {
+                        Player(1) => 'x',
+                        Player(2) => 'o',
+                        _ => '.',
+                    };
+                    if last_move == Some((column, row)) { stone.to_ascii_uppercase() } else { stone }
+                })
+                .collect())
+            .collect();
+        format!("{} {}", self.win_length(), rows.join("/"))
+    }
+
+    /// Only accepts positions that can be reached by playing one stone after another, with
+    /// the upper case stone on top of its column.
+    fn from_notation(notation: &str) -> Result<ConnectFour<S>, String> {
+        let (win_length, board) = notation.trim().split_once(' ').ok_or("missing win length")?;
+        let win_length = win_length.parse().map_err(|_| format!("invalid win length `{}`", win_length))?;
+        let rows: Vec<Vec<char>> = board.split('/').map(|row| row.chars().collect()).collect();
+        let columns = rows[0].len();
+        if columns == 0 {
+            return Err("empty board".to_string());
+        }
+        if rows.iter().any(|row| row.len() != columns) {
+            return Err("rows of different lengths".to_string());
+        }
+        let mut game = ConnectFour::<S>::with_win_length(columns, rows.len(), win_length)
+            .map_err(|()| format!("unsupported board {}x{} with win length {}", columns, rows.len(), win_length))?;
+
+        let mut last_stone = None;
+        let mut stones = [0, 0];
+        for column in 0..columns {
+            let mut empty_below = false;
+            for row in rows.iter().rev() {
+                let player = match row[column] {
+                    '.' => {
+                        empty_below = true;
+                        continue;
+                    }
+                    'x' | 'X' => Player(1),
+                    'o' | 'O' => Player(2),
+                    cell => return Err(format!("invalid cell `{}`", cell)),
+                };
+                if empty_below || last_stone.is_some_and(|(last_column, _)| last_column == column) {
+                    return Err(format!("column {} has stones above empty cells", column));
+                }
+                stones[player.0 as usize - 1] += 1;
+                if row[column].is_ascii_uppercase() {
+                    if last_stone.is_some() {
+                        return Err("more than one last stone".to_string());
+                    }
+                    last_stone = Some((column, player));
+                }
+                else {
+                    game.state.play(column, player).map_err(|err| format!("{:?}", err))?;
+                }
+            }
+        }
+
+        // The first player moves first, so they have as many stones as their opponent or one
+        // more, and the last stone is theirs exactly if they have more.
+        let last_player = if stones[0] == stones[1] + 1 { Player(1) } else { Player(2) };
+        match last_stone {
+            _ if stones[0] != stones[1] && stones[0] != stones[1] + 1 => {
+                return Err("the players have impossible numbers of stones".to_string());
+            }
+            None if stones[0] + stones[1] > 0 => return Err("missing last stone".to_string()),
+            Some((_, player)) if player != last_player => return Err("the last stone belongs to the wrong player".to_string()),
+            Some((column, player)) => {
+                game.state.play(column, player).map_err(|err| format!("{:?}", err))?;
+                if game.state.has_just_won() {
+                    game.winner = Some(player);
+                }
+            }
+            None => {}
+        }
+        game.current_player = Player(3 - last_player.0);
+        Ok(game)
+    }
+}
+
+/// Positions are stored as the board size and win length, followed by the stones column by
+/// column from bottom to top, so that loading can replay them through `State::play` regardless
+/// of the state representation.
+impl<S: State> Persist for ConnectFour<S> {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let (columns, rows) = self.size();
+        (columns as u8).write_to(writer)?;
+        (rows as u8).write_to(writer)?;
+        (self.win_length() as u8).write_to(writer)?;
+        self.current_player.write_to(writer)?;
+        self.winner.unwrap_or(Player(0)).write_to(writer)?;
+        for column in 0..columns {
+            let stones: Vec<_> = (0..rows).rev()
+                .map(|row| self.state.get(column, row))
+                .take_while(|&player| player != Player(0))
+                .collect();
+            (stones.len() as u8).write_to(writer)?;
+            for player in stones {
+                player.write_to(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let columns = u8::read_from(reader)? as usize;
+        let rows = u8::read_from(reader)? as usize;
+        let win_length = u8::read_from(reader)? as usize;
+        let mut game = ConnectFour::<S>::with_win_length(columns, rows, win_length)
+            .map_err(|()| invalid_data("unsupported board size or win length"))?;
+        game.current_player = match Player::read_from(reader)? {
+            player @ (Player(1) | Player(2)) => player,
+            _ => return Err(invalid_data("invalid player to move")),
+        };
+        game.winner = match Player::read_from(reader)? {
+            Player(0) => None,
+            winner @ (Player(1) | Player(2)) => Some(winner),
+            _ => return Err(invalid_data("invalid winner")),
+        };
+        for column in 0..columns {
+            let height = u8::read_from(reader)? as usize;
+            if height > rows {
+                return Err(invalid_data("column contains more stones than rows"));
+            }
+            for _ in 0..height {
+                let player = Player::read_from(reader)?;
+                if player != Player(1) && player != Player(2) {
+                    return Err(invalid_data("invalid player"));
+                }
+                game.state.play(column, player)
+                    .map_err(|_| invalid_data("column contains more stones than rows"))?;
+            }
+        }
+        Ok(game)
+    }
+}
+
 
 pub trait State : fmt::Display + Clone + Send + Sync {
+    #[allow(clippy::result_unit_err)]
     fn new(columns: usize, rows: usize) -> Result<Self, ()>;
     fn size(&self) -> (usize, usize);
     fn set(&mut self, column: usize, row: usize, player: Player);
     fn get(&self, column: usize, row: usize) -> Player;
     fn last_move(&self) -> (usize, usize);
 
+    /// Creates a board on which `win_length` stones in a line win. The default implementation
+    /// only supports the usual four.
+    #[allow(clippy::result_unit_err)]
+    fn with_win_length(columns: usize, rows: usize, win_length: usize) -> Result<Self, ()> {
+        if win_length == 4 {
+            Self::new(columns, rows)
+        }
+        else {
+            Err(())
+        }
+    }
+
+    /// The number of stones in a line needed to win.
+    fn win_length(&self) -> usize {
+        4
+    }
+
+    /// A key identifying the stones on the board, see `PositionKey`.
+    fn key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.size().hash(&mut hasher);
+        for column in 0..self.size().0 {
+            for row in 0..self.size().1 {
+                self.get(column, row).0.hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
     fn row(&self, row: usize) -> Option<Box<[Player]>> {
         if row >= self.size().1 {
             return None;
@@ -122,6 +354,7 @@ pub trait State : fmt::Display + Clone + Send + Sync {
     // licensed under the MIT License.
     #[inline(never)]
     fn has_just_won(&self) -> bool {
+        let win_length = self.win_length() as isize;
         let (last_column, last_row) = self.last_move();
         let player = self.get(last_column, last_row);
         let (last_column, last_row) = (last_column as isize, last_row as isize);
@@ -141,7 +374,7 @@ pub trait State : fmt::Display + Clone + Send + Sync {
                 right += 1;
                 column += 1;
             }
-            if left + right + 1 >= 4 {
+            if left + right + 1 >= win_length {
                 return true;
             }
         }
@@ -158,7 +391,7 @@ pub trait State : fmt::Display + Clone + Send + Sync {
                 up += 1;
                 row += 1;
             }
-            if up + down + 1 >= 4 {
+            if up + down + 1 >= win_length {
                 return true;
             }
         }
@@ -183,7 +416,7 @@ pub trait State : fmt::Display + Clone + Send + Sync {
                 column += 1;
                 row += 1;
             }
-            if up + down + 1 >= 4 {
+            if up + down + 1 >= win_length {
                 return true;
             }
         }
@@ -208,12 +441,12 @@ pub trait State : fmt::Display + Clone + Send + Sync {
                 column += 1;
                 row -= 1;
             }
-            if up + down + 1 >= 4 {
+            if up + down + 1 >= win_length {
                 return true;
             }
         }
 
-        return false;
+        false
     }
 
     fn validate_move(&self, column_number: usize) -> Result<(), InvalidMove> {
@@ -229,6 +462,25 @@ pub trait State : fmt::Display + Clone + Send + Sync {
         }
     }
 
+    /// The columns in which `player` would complete a line of four with their next stone.
+    fn winning_columns(&self, player: Player) -> Vec<usize> {
+        self.valid_moves()
+            .into_iter()
+            .filter(|&column| {
+                let mut state = self.clone();
+                state.play(column, player).is_ok() && state.has_just_won()
+            })
+            .collect()
+    }
+
+    /// The number of cells without a stone.
+    fn empty_cells(&self) -> usize {
+        let (columns, rows) = self.size();
+        (0..columns)
+            .map(|column| (0..rows).take_while(|&row| self.get(column, row) == Player(0)).count())
+            .sum()
+    }
+
     fn valid_moves(&self) -> Vec<usize> {
         let columns = self.size().0;
         let mut moves = Vec::with_capacity(columns);
@@ -240,6 +492,7 @@ pub trait State : fmt::Display + Clone + Send + Sync {
         // Cannot `filter()` and `collect()` here as `Filter::size_hint()` returns a lower bound
         // of 0, which means the `Vec` has to realloc several times.
         // The explicit loop is also slightly faster than an `extend` with `filter` and `map`.
+        valid_moves.clear();
         let columns = self.size().0;
         for i in 0..columns {
             if self.get(i, 0) == Player(0) {
@@ -249,48 +502,52 @@ pub trait State : fmt::Display + Clone + Send + Sync {
     }
 
     fn _fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let (n_columns, n_rows) = self.size();
-        let fill_row = |left, joiner, right| {
-            format!("{}{}{}", left, vec!["─"; n_columns].join(joiner), right)
-        };
-
-        let rows: Vec<_> = (0..n_rows).map(|i| {
-            let row = self.row(i).unwrap_or_else(|| unreachable!());
-            let positions: Vec<_> = row.iter().map(|p| format!("{}", p)).collect();
-            format!("│{}│\n", positions.join("│"))
-        }).collect();
-
-        write!(
-            f, "{top_row}{body}{bottom_row}",
-            top_row = fill_row("┌", "┬", "┐\n"),
-            body = rows.join(&fill_row("├", "┼", "┤\n")),
-            bottom_row = fill_row("└", "┴", "┘\n")
-        )?;
-        write!(f, " {}\n", (0..n_columns).map(|n| format!("{}", n)).collect::<Vec<_>>().join(" "))
+        write!(f, "{}", render::Ascii.board(&render::Board::new(self)))
     }
 }
 
 
+/// The largest number of columns and of rows a `VecState` supports. Larger boards are useless
+/// for play and would make `rows * columns` overflow or exhaust the memory.
+pub const MAX_BOARD_SIDE: usize = 64;
+
+
 #[derive(Clone, Debug)]
 pub struct VecState {
     state: Vec<Player>,
     columns: usize,
     rows: usize,
     last_move: (usize, usize),
+    win_length: usize,
 }
 
 impl State for VecState {
     fn new(columns: usize, rows: usize) -> Result<Self, ()> {
+        Self::with_win_length(columns, rows, 4)
+    }
+
+    /// Supports boards with 1 to `MAX_BOARD_SIDE` columns and rows and every win length of at
+    /// least two.
+    fn with_win_length(columns: usize, rows: usize, win_length: usize) -> Result<Self, ()> {
+        let sides = 1..=MAX_BOARD_SIDE;
+        if win_length < 2 || !sides.contains(&columns) || !sides.contains(&rows) {
+            return Err(());
+        }
         Ok(
             VecState {
                 state: vec![Player(0); rows * columns],
-                columns: columns,
-                rows: rows,
-                last_move: (0, 0)
+                columns,
+                rows,
+                last_move: (0, 0),
+                win_length,
             }
         )
     }
 
+    fn win_length(&self) -> usize {
+        self.win_length
+    }
+
     fn size(&self) -> (usize, usize) {
         (self.columns, self.rows)
     }
@@ -330,7 +587,7 @@ pub struct BitState {
 
 impl State for BitState {
     fn new(columns: usize, rows: usize) -> Result<Self, ()> {
-        if columns * (rows + 1) > 64 || columns > 12 {
+        if columns > 12 || rows >= 64 || columns * (rows + 1) > 64 {
             Err(())
         }
         else {
@@ -388,7 +645,58 @@ impl State for BitState {
     }
 
     fn last_move(&self) -> (usize, usize) {
-        (self.last_column as usize, self.empty_per_column[self.last_column as usize] as usize + 1)
+        if self.last_column == 255 {
+            return (0, 0);
+        }
+        (self.last_column as usize, self.empty_per_column[self.last_column as usize] as usize)
+    }
+
+    fn empty_cells(&self) -> usize {
+        self.columns as usize * self.rows as usize - self.stones()
+    }
+
+    /// Finds the cells that complete a line of four with shifted bitboards instead of trying
+    /// every column.
+    fn winning_columns(&self, Player(player): Player) -> Vec<usize> {
+        let height = self.rows as u32 + 1;
+        let column_mask = (1 << self.rows) - 1;
+        let board = (0..self.columns as u32).fold(0, |board, column| board | column_mask << (column * height));
+        let player = player as usize - 1;
+        let threats = eval::threats_of(self.state[player].0, self.state[1 - player].0, self.rows, board);
+        self.empty_per_column[..self.columns as usize].iter()
+            .enumerate()
+            .filter(|&(column, &empty)| empty != 0 && threats & 1 << (column as u32 * height + empty as u32 - 1) != 0)
+            .map(|(column, _)| column)
+            .collect()
+    }
+
+    /// An exact encoding of the position: Every column is stored in `rows + 1` bits as the
+    /// stones of the first player followed by a marker bit above the topmost stone.
+    fn key(&self) -> u64 {
+        (0..self.columns as usize).rev()
+            .fold(0, |key, column| (key << (self.rows + 1)) | self.column_key(column))
+    }
+}
+
+impl BitState {
+    /// The key of the position reflected at the centre column, see `State::key`.
+    pub fn mirrored_key(&self) -> u64 {
+        (0..self.columns as usize)
+            .fold(0, |key, column| (key << (self.rows + 1)) | self.column_key(column))
+    }
+
+    /// The number of stones on the board.
+    pub fn stones(&self) -> usize {
+        self.empty_per_column[..self.columns as usize].iter()
+            .map(|&empty| (self.rows - empty) as usize)
+            .sum()
+    }
+
+    fn column_key(&self, column: usize) -> u64 {
+        let rows = self.rows as u64;
+        let height = rows - self.empty_per_column[column] as u64;
+        let stones = (self.state[0].0 >> (column as u64 * (rows + 1) + rows - height)) & ((1 << height) - 1);
+        stones | (1 << height)
     }
 }
 
@@ -406,15 +714,15 @@ impl BitBoard {
     #[inline(always)]
     fn has_winner(&self, rows: u8) -> bool {
         let x = self.0;
-        let vertical = x & x >> 1;
-        let horizontal = x & x >> rows + 1;
-        let diagonal_ud = x & x >> rows;
-        let diagonal_du = x & x >> rows + 2;
-
-        (vertical & vertical >> 2)
-        | (horizontal & horizontal >> 2 * (rows + 1))
-        | (diagonal_ud & diagonal_ud >> 2 * rows)
-        | (diagonal_du & diagonal_du >> 2 * (rows + 2))
+        let vertical = x & (x >> 1);
+        let horizontal = x & (x >> (rows + 1));
+        let diagonal_ud = x & (x >> rows);
+        let diagonal_du = x & (x >> (rows + 2));
+
+        (vertical & (vertical >> 2))
+        | (horizontal & (horizontal >> (2 * (rows + 1))))
+        | (diagonal_ud & (diagonal_ud >> (2 * rows)))
+        | (diagonal_du & (diagonal_du >> (2 * (rows + 2))))
         != 0
     }
 
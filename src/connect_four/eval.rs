@@ -0,0 +1,286 @@
+//! Heuristic evaluation of Connect Four positions.
+//!
+//! All features are computed on the bitboards of a `BitState`. Every column occupies
+//! `rows + 1` bits: the topmost row has the lowest bit of a column, the bottom row bit
+//! `rows - 1`, and the additional bit `rows` below the bottom row is always empty, which keeps
+//! lines from wrapping around between columns.
+//!
+//! Rows are counted from the bottom starting at one, so that the classic parity rules apply:
+//! The first player profits from threats in odd rows, the second player from threats in even
+//! rows.
+
+use super::{BitState, ConnectFour, State};
+use crate::{Evaluate, Game, Player};
+
+/// Scores at least this large mean that the game is decided.
+pub const WIN_SCORE: i32 = 1_000;
+
+const THREE_WEIGHT: i32 = 4;
+const TWO_WEIGHT: i32 = 1;
+const THREAT_WEIGHT: i32 = 8;
+const GOOD_THREAT_WEIGHT: i32 = 12;
+const CENTRE_WEIGHT: i32 = 1;
+
+
+/// Features of one player’s stones.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Features {
+    /// Lines of four cells with three own stones and one empty cell.
+    pub open_threes: u32,
+    /// Lines of four cells with two own stones and two empty cells.
+    pub open_twos: u32,
+    /// Empty cells completing a line of four in odd rows.
+    pub odd_threats: u32,
+    /// Empty cells completing a line of four in even rows.
+    pub even_threats: u32,
+    /// Threats that can be played right now.
+    pub immediate_wins: u32,
+    /// Sum of the stones’ column weights, which are highest in the centre.
+    pub centre: u32,
+}
+
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Evaluation {
+    pub players: [Features; 2],
+    pub to_move: Player,
+}
+
+impl Evaluation {
+    pub fn features(&self, Player(player): Player) -> &Features {
+        &self.players[player as usize - 1]
+    }
+
+    /// The heuristic value of the position for `player`. Positive values are good for `player`,
+    /// the values for both players always sum to zero.
+    pub fn score(&self, player: Player) -> i32 {
+        let to_move = self.features(self.to_move);
+        let waiting = self.features(Player(3 - self.to_move.0));
+        let score_for_player_to_move =
+            if to_move.immediate_wins > 0 {
+                WIN_SCORE
+            }
+            else if waiting.immediate_wins > 1 {
+                -WIN_SCORE
+            }
+            else {
+                self.heuristic(self.to_move) - self.heuristic(Player(3 - self.to_move.0))
+            };
+
+        if player == self.to_move {
+            score_for_player_to_move
+        }
+        else {
+            -score_for_player_to_move
+        }
+    }
+
+    fn heuristic(&self, player: Player) -> i32 {
+        let features = self.features(player);
+        let (good_threats, bad_threats) = if player == Player(1) {
+            (features.odd_threats, features.even_threats)
+        }
+        else {
+            (features.even_threats, features.odd_threats)
+        };
+        THREE_WEIGHT * features.open_threes as i32
+            + TWO_WEIGHT * features.open_twos as i32
+            + GOOD_THREAT_WEIGHT * good_threats as i32
+            + THREAT_WEIGHT * bad_threats as i32
+            + CENTRE_WEIGHT * features.centre as i32
+    }
+}
+
+
+/// Precomputed masks for evaluating positions of one board size.
+#[derive(Clone, Debug)]
+pub struct Evaluator {
+    columns: u8,
+    rows: u8,
+    board: u64,
+    bottom: u64,
+    odd_rows: u64,
+    windows: Vec<u64>,
+    column_weights: Vec<(u64, u32)>,
+}
+
+impl Evaluator {
+    pub fn new(columns: usize, rows: usize) -> Evaluator {
+        let (columns, rows) = (columns as u8, rows as u8);
+        let height = rows as u32 + 1;
+        let cell = |column: u8, row: u8| 1u64 << (column as u32 * height + row as u32);
+        let column_mask = |column: u8| (0..rows).fold(0, |mask, row| mask | cell(column, row));
+
+        let board = (0..columns).fold(0, |mask, column| mask | column_mask(column));
+        let bottom = (0..columns).fold(0, |mask, column| mask | cell(column, rows - 1));
+        let odd_rows = (0..columns)
+            .flat_map(|column| (0..rows).map(move |row| (column, row)))
+            .filter(|&(_, row)| (rows - row) % 2 == 1)
+            .fold(0, |mask, (column, row)| mask | cell(column, row));
+
+        let directions: [(i32, i32); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let mut windows = vec![];
+        for column in 0..columns as i32 {
+            for row in 0..rows as i32 {
+                for &(d_column, d_row) in directions.iter() {
+                    let end_column = column + 3 * d_column;
+                    let end_row = row + 3 * d_row;
+                    if end_column < columns as i32 && (0..rows as i32).contains(&end_row) {
+                        windows.push(
+                            (0..4).fold(0, |mask, i| {
+                                mask | cell((column + i * d_column) as u8, (row + i * d_row) as u8)
+                            })
+                        );
+                    }
+                }
+            }
+        }
+
+        let column_weights = (0..columns)
+            .map(|column| {
+                let distance = (2 * column as i32 - (columns as i32 - 1)).unsigned_abs();
+                (column_mask(column), columns as u32 - distance)
+            })
+            .collect();
+
+        Evaluator { columns, rows, board, bottom, odd_rows, windows, column_weights }
+    }
+
+    pub fn for_state(state: &BitState) -> Evaluator {
+        let (columns, rows) = state.size();
+        Evaluator::new(columns, rows)
+    }
+
+    pub fn evaluate(&self, state: &BitState, player: Player) -> i32 {
+        self.features(state).score(player)
+    }
+
+    pub fn features(&self, state: &BitState) -> Evaluation {
+        debug_assert_eq!((state.columns, state.rows), (self.columns, self.rows));
+        let stones = [state.state[0].0, state.state[1].0];
+        let to_move = if stones[0].count_ones() == stones[1].count_ones() {
+            Player(1)
+        }
+        else {
+            Player(2)
+        };
+        let playable = self.playable(state);
+
+        let mut players = [Features::default(); 2];
+        for (player, features) in players.iter_mut().enumerate() {
+            let own = stones[player];
+            let opponent = stones[1 - player];
+            for &window in self.windows.iter() {
+                if window & opponent == 0 {
+                    match (window & own).count_ones() {
+                        3 => features.open_threes += 1,
+                        2 => features.open_twos += 1,
+                        _ => {}
+                    }
+                }
+            }
+            let threats = self.threats_of(own, opponent);
+            features.odd_threats = (threats & self.odd_rows).count_ones();
+            features.even_threats = (threats & !self.odd_rows).count_ones();
+            features.immediate_wins = (threats & playable).count_ones();
+            features.centre = self.column_weights.iter()
+                .map(|&(mask, weight)| (own & mask).count_ones() * weight)
+                .sum();
+        }
+
+        Evaluation { players, to_move }
+    }
+
+    /// The empty cells where `player` would complete a line of four.
+    pub fn threats(&self, state: &BitState, Player(player): Player) -> u64 {
+        let player = player as usize - 1;
+        self.threats_of(state.state[player].0, state.state[1 - player].0)
+    }
+
+    fn threats_of(&self, own: u64, opponent: u64) -> u64 {
+        threats_of(own, opponent, self.rows, self.board)
+    }
+
+    fn playable(&self, state: &BitState) -> u64 {
+        let occupied = state.state[0].0 | state.state[1].0;
+        !occupied & self.board & ((occupied >> 1) | self.bottom)
+    }
+}
+
+/// The empty cells of `board` where the stones `own` would complete a line of four, for boards
+/// with `rows` rows in the bit layout of `BitState`.
+pub(crate) fn threats_of(own: u64, opponent: u64, rows: u8, board: u64) -> u64 {
+    let height = rows as u32 + 1;
+    let mut threats = 0;
+    for &shift in [1, height, height - 1, height + 1].iter() {
+        let shifted = |n: u32| (
+            own.checked_shl(n * shift).unwrap_or(0),
+            own.checked_shr(n * shift).unwrap_or(0),
+        );
+        let ((up_1, down_1), (up_2, down_2), (up_3, down_3)) = (shifted(1), shifted(2), shifted(3));
+        threats |= up_1 & up_2 & up_3;
+        threats |= down_1 & down_2 & down_3;
+        threats |= up_1 & up_2 & down_1;
+        threats |= down_1 & down_2 & up_1;
+    }
+    threats & board & !(own | opponent)
+}
+
+
+impl Evaluate<ConnectFour<BitState>> for Evaluator {
+    fn evaluate(&self, game: &ConnectFour<BitState>, player: Player) -> i32 {
+        self.features(game.state()).score(player)
+    }
+}
+
+
+/// Evaluates `state` for `player` without reusing precomputed masks.
+pub fn evaluate(state: &BitState, player: Player) -> i32 {
+    Evaluator::for_state(state).evaluate(state, player)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_open_three_in_bottom_row() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[0, 0, 1, 1, 2]);
+        let evaluation = Evaluator::for_state(game.state()).features(game.state());
+        let first = evaluation.features(Player(1));
+        assert_eq!(evaluation.to_move, Player(2));
+        assert_eq!(first.open_threes, 1);
+        assert_eq!(first.odd_threats, 1);
+        assert_eq!(first.even_threats, 0);
+        assert_eq!(first.immediate_wins, 1);
+        assert_eq!(evaluation.features(Player(2)).immediate_wins, 0);
+        assert_eq!(game.state().winning_columns(Player(1)), vec![3]);
+    }
+
+    #[test]
+    fn threats_do_not_wrap_around_columns() {
+        // The bottom of column 0 is not adjacent to the top of column 1.
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[0, 1, 0, 1, 0]);
+        let evaluator = Evaluator::for_state(game.state());
+        assert_eq!(evaluator.threats(game.state(), Player(1)), 1 << 2);
+        assert_eq!(evaluator.threats(game.state(), Player(2)), 0);
+    }
+
+    #[test]
+    fn scores_are_antisymmetric_and_prefer_the_centre() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[3]);
+        let centre = evaluate(game.state(), Player(1));
+        assert!(centre > 0);
+        assert_eq!(centre, -evaluate(game.state(), Player(2)));
+        assert!(centre > evaluate(ConnectFour::<BitState>::from_moves(7, 6, &[0]).state(), Player(1)));
+    }
+
+    #[test]
+    fn immediate_wins_decide_the_game() {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[1, 1, 2, 2, 3]);
+        assert_eq!(evaluate(game.state(), Player(2)), -WIN_SCORE);
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[0, 6, 0, 6, 0, 5]);
+        assert_eq!(evaluate(game.state(), Player(1)), WIN_SCORE);
+    }
+}
@@ -0,0 +1,311 @@
+//! Text renderings of boards for terminals, log files and other programs.
+//!
+//! Every renderer highlights the stone of the last move and the stones of a winning line. The
+//! `Display` implementations of the states use the `Ascii` renderer, so that logs stay
+//! readable everywhere.
+
+use super::State;
+use crate::Player;
+
+
+/// Why a cell stands out.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Highlight {
+    None,
+    LastMove,
+    /// Takes precedence over `LastMove`, which is always part of the winning line.
+    WinningLine,
+}
+
+
+/// A board as seen by a renderer: the stones with their highlights.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Board {
+    columns: usize,
+    rows: usize,
+    /// Row by row, starting with the top row.
+    cells: Vec<(Player, Highlight)>,
+}
+
+impl Board {
+    pub fn new<S: State>(state: &S) -> Board {
+        let (columns, rows) = state.size();
+        let mut cells: Vec<_> = (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| (state.get(column, row), Highlight::None))
+            .collect();
+        if state.empty_cells() < columns * rows {
+            let (column, row) = state.last_move();
+            cells[row * columns + column].1 = Highlight::LastMove;
+            for (column, row) in winning_line(state) {
+                cells[row * columns + column].1 = Highlight::WinningLine;
+            }
+        }
+        Board { columns, rows, cells }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    /// Rows are numbered from the top.
+    pub fn get(&self, column: usize, row: usize) -> (Player, Highlight) {
+        self.cells[row * self.columns + column]
+    }
+
+    fn rows(&self) -> impl Iterator<Item=&[(Player, Highlight)]> {
+        self.cells.chunks(self.columns)
+    }
+}
+
+/// The cells of all lines through the last move that are long enough to win.
+fn winning_line<S: State>(state: &S) -> Vec<(usize, usize)> {
+    let (columns, rows) = state.size();
+    let (last_column, last_row) = state.last_move();
+    let player = state.get(last_column, last_row);
+    let same = |column: isize, row: isize| {
+        column >= 0 && row >= 0 && (column as usize) < columns && (row as usize) < rows
+            && state.get(column as usize, row as usize) == player
+    };
+
+    let mut line = vec![];
+    for &(d_column, d_row) in &[(1, 0), (0, 1), (1, 1), (1, -1)] {
+        let (mut column, mut row) = (last_column as isize, last_row as isize);
+        while same(column - d_column, row - d_row) {
+            column -= d_column;
+            row -= d_row;
+        }
+        let mut cells = vec![];
+        while same(column, row) {
+            cells.push((column as usize, row as usize));
+            column += d_column;
+            row += d_row;
+        }
+        if cells.len() >= state.win_length() {
+            line.extend(cells);
+        }
+    }
+    line
+}
+
+
+pub trait Renderer: Send + Sync {
+    /// How `player` is called in messages, matching their stones on the board.
+    fn player(&self, player: Player) -> String;
+
+    /// The whole board, ending with a line break.
+    fn board(&self, board: &Board) -> String;
+}
+
+
+/// The column numbers centred below cells that are three characters wide.
+fn column_numbers(columns: usize) -> String {
+    let numbers: String = (0..columns).map(|column| format!("{:^3}", column)).collect();
+    format!("{}\n", numbers.trim_end())
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Palette {
+    /// Blue and red.
+    Standard,
+    /// Blue and orange, which can also be told apart with red-green colour blindness.
+    ColourBlind,
+}
+
+/// Coloured stones in a grid of box drawing characters for terminals that understand ANSI
+/// escape sequences. The last move is underlined, winning lines are shown in reverse video.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ansi {
+    pub palette: Palette,
+}
+
+impl Default for Ansi {
+    fn default() -> Ansi {
+        Ansi { palette: Palette::Standard }
+    }
+}
+
+impl Ansi {
+    fn colour(&self, Player(player): Player) -> &'static str {
+        match (self.palette, player) {
+            (Palette::Standard, 1) => "\x1B[44;1m",
+            (Palette::Standard, 2) => "\x1B[41;1m",
+            (Palette::ColourBlind, 1) => "\x1B[48;5;25;1m",
+            (Palette::ColourBlind, 2) => "\x1B[48;5;208;1m",
+            _ => "",
+        }
+    }
+
+    fn cell(&self, player: Player, highlight: Highlight) -> String {
+        let highlight = match highlight {
+            Highlight::None => "",
+            Highlight::LastMove => "\x1B[4m",
+            Highlight::WinningLine => "\x1B[7m",
+        };
+        format!("{}{}{}\x1B[0m", self.colour(player), highlight, Ascii::symbol(player))
+    }
+}
+
+impl Renderer for Ansi {
+    fn player(&self, player: Player) -> String {
+        self.cell(player, Highlight::None)
+    }
+
+    fn board(&self, board: &Board) -> String {
+        let (columns, _) = board.size();
+        let fill_row = |left, joiner, right| {
+            format!("{}{}{}\n", left, vec!["─"; columns].join(joiner), right)
+        };
+
+        let rows: Vec<_> = board.rows().map(|row| {
+            let cells: Vec<_> = row.iter().map(|&(player, highlight)| self.cell(player, highlight)).collect();
+            format!("│{}│\n", cells.join("│"))
+        }).collect();
+        let numbers: Vec<_> = (0..columns).map(|column| column.to_string()).collect();
+
+        format!(
+            "{}{}{} {}\n",
+            fill_row("┌", "┬", "┐"),
+            rows.join(&fill_row("├", "┼", "┤")),
+            fill_row("└", "┴", "┘"),
+            numbers.join(" "),
+        )
+    }
+}
+
+
+/// `X`, `O` and `.` on any terminal. The last move is put in parentheses, winning lines in
+/// brackets.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Ascii;
+
+impl Ascii {
+    fn symbol(Player(player): Player) -> &'static str {
+        match player {
+            1 => "X",
+            2 => "O",
+            _ => " ",
+        }
+    }
+}
+
+impl Renderer for Ascii {
+    fn player(&self, player: Player) -> String {
+        Ascii::symbol(player).to_string()
+    }
+
+    fn board(&self, board: &Board) -> String {
+        let mut text = String::new();
+        for row in board.rows() {
+            let cells: String = row.iter()
+                .map(|&(player, highlight)| {
+                    let symbol = if player == Player(0) { "." } else { Ascii::symbol(player) };
+                    match highlight {
+                        Highlight::None => format!(" {} ", symbol),
+                        Highlight::LastMove => format!("({})", symbol),
+                        Highlight::WinningLine => format!("[{}]", symbol),
+                    }
+                })
+                .collect();
+            text += cells.trim_end();
+            text += "\n";
+        }
+        text + &column_numbers(board.size().0)
+    }
+}
+
+
+/// Black and white discs on dots. The last disc is drawn with a ring around it, discs in a
+/// winning line as diamonds.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Unicode;
+
+impl Unicode {
+    fn symbol(Player(player): Player, highlight: Highlight) -> &'static str {
+        match (player, highlight) {
+            (1, Highlight::None) => "●",
+            (1, Highlight::LastMove) => "◉",
+            (1, Highlight::WinningLine) => "◆",
+            (2, Highlight::None) => "○",
+            (2, Highlight::LastMove) => "◎",
+            (2, Highlight::WinningLine) => "◇",
+            _ => "·",
+        }
+    }
+}
+
+impl Renderer for Unicode {
+    fn player(&self, player: Player) -> String {
+        Unicode::symbol(player, Highlight::None).to_string()
+    }
+
+    fn board(&self, board: &Board) -> String {
+        let mut text = String::new();
+        for row in board.rows() {
+            let cells: Vec<_> = row.iter().map(|&(player, highlight)| Unicode::symbol(player, highlight)).collect();
+            text += &format!(" {}\n", cells.join("  "));
+        }
+        text + &column_numbers(board.size().0)
+    }
+}
+
+
+/// The board on a single line for logs and other programs: the rows from top to bottom
+/// separated by `/`, with `x`, `o` and `.` for the cells. Highlighted stones are upper case.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Compact;
+
+impl Renderer for Compact {
+    fn player(&self, Player(player): Player) -> String {
+        match player {
+            1 => "x",
+            2 => "o",
+            _ => ".",
+        }.to_string()
+    }
+
+    fn board(&self, board: &Board) -> String {
+        let rows: Vec<String> = board.rows()
+            .map(|row| row.iter()
+                .map(|&(player, highlight)| {
+                    let symbol = self.player(player);
+                    if highlight == Highlight::None { symbol } else { symbol.to_uppercase() }
+                })
+                .collect())
+            .collect();
+        format!("{}\n", rows.join("/"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitState, ConnectFour, Game, VecState};
+
+    #[test]
+    fn renderers_highlight_the_last_move() {
+        let board = Board::new(ConnectFour::<BitState>::from_moves(4, 4, &[1, 2]).state());
+        assert_eq!(board.get(1, 3), (Player(1), Highlight::None));
+        assert_eq!(board.get(2, 3), (Player(2), Highlight::LastMove));
+        assert_eq!(Compact.board(&board), "..../..../..../.xO.\n");
+        assert_eq!(Ascii.board(&board), " .  .  .  .\n .  .  .  .\n .  .  .  .\n .  X (O) .\n 0  1  2  3\n");
+        assert_eq!(Unicode.board(&board).lines().nth(3), Some(" ·  ●  ◎  ·"));
+        assert!(Ansi::default().board(&board).contains("\x1B[41;1m\x1B[4mO\x1B[0m"));
+        assert_eq!(Compact.board(&Board::new(ConnectFour::<BitState>::from_moves(4, 4, &[]).state())), "..../..../..../....\n");
+    }
+
+    #[test]
+    fn renderers_highlight_winning_lines() {
+        let game = ConnectFour::<BitState>::from_moves(4, 4, &[0, 1, 1, 2, 3, 2, 2, 3, 3, 0, 3]);
+        assert_eq!(game.winner(), Some(Player(1)));
+        assert_eq!(Compact.board(&Board::new(game.state())), "...X/..Xx/oXoo/Xoox\n");
+
+        let mut game = ConnectFour::<VecState>::with_win_length(5, 2, 3).unwrap();
+        for &move_ in &[0, 0, 1, 1, 2] {
+            game.play(move_).unwrap();
+        }
+        assert_eq!(Compact.board(&Board::new(game.state())), "oo.../XXX..\n");
+    }
+}
@@ -0,0 +1,186 @@
+//! Datasets of recorded games for training evaluation models offline.
+//!
+//! Every position of a game becomes one `Record`. Records can be written as CSV with
+//! `CSV_HEADER` as the first line, or in a compact binary format: the magic bytes `MCC4DATA`,
+//! the format version, the number of columns and rows as `u8`, and then the records back to
+//! back. A binary record consists of the game index and the ply as `u32`, the player to move
+//! as `u8`, one `u8` per cell, one `u32` visit count per column, the column that was played as
+//! `u8` and the result as `i8`. All integers are little endian.
+
+use std::io;
+use std::io::prelude::*;
+
+use crate::persist::Persist;
+use crate::{ConnectFour, Game, Player, PlayerTrait, State};
+
+const DATASET_MAGIC: &[u8; 8] = b"MCC4DATA";
+const DATASET_FORMAT_VERSION: u16 = 1;
+
+pub const CSV_HEADER: &str = "game,ply,to_move,board,visits,move,result";
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub game: u32,
+    pub ply: u32,
+    pub to_move: Player,
+    /// The cells column by column, each column from the top row down. Empty cells are `0`,
+    /// occupied cells hold the number of the player.
+    pub board: Vec<u8>,
+    /// The number of times the search visited each column. Players that do not search report
+    /// a single visit for the move they played.
+    pub visits: Vec<u32>,
+    /// The column that was played.
+    pub move_: usize,
+    /// The final result for the player to move: `1` for a win, `0` for a draw and `-1` for a
+    /// loss.
+    pub result: i8,
+}
+
+impl Record {
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.game.write_to(writer)?;
+        self.ply.write_to(writer)?;
+        self.to_move.write_to(writer)?;
+        writer.write_all(&self.board)?;
+        for visits in &self.visits {
+            visits.write_to(writer)?;
+        }
+        (self.move_ as u8).write_to(writer)?;
+        (self.result as u8).write_to(writer)
+    }
+
+    /// Writes the record as one CSV line. The board is written as one digit per cell and the
+    /// visits are separated by spaces.
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let board: String = self.board.iter().map(|&cell| char::from(b'0' + cell)).collect();
+        let visits: Vec<_> = self.visits.iter().map(|visits| visits.to_string()).collect();
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            self.game,
+            self.ply,
+            self.to_move.0,
+            board,
+            visits.join(" "),
+            self.move_,
+            self.result,
+        )
+    }
+}
+
+
+/// Writes the header of the binary format for games on boards of the given size.
+pub fn write_binary_header<W: Write>(writer: &mut W, columns: usize, rows: usize) -> io::Result<()> {
+    writer.write_all(DATASET_MAGIC)?;
+    DATASET_FORMAT_VERSION.write_to(writer)?;
+    (columns as u8).write_to(writer)?;
+    (rows as u8).write_to(writer)
+}
+
+
+/// Plays `game` to the end between `first` and `second`, where `first` is the player to move,
+/// and records every position.
+pub fn record_game<S: State>(
+    index: u32,
+    mut game: ConnectFour<S>,
+    first: &dyn PlayerTrait<Game = ConnectFour<S>>,
+    second: &dyn PlayerTrait<Game = ConnectFour<S>>,
+) -> Vec<Record> {
+    let (columns, rows) = game.size();
+    let first_player = game.current_player();
+    let mut records = vec![];
+    while !game.has_ended() {
+        let player = if game.current_player() == first_player { first } else { second };
+        let (move_, statistics) = player.analyse(&game);
+        let mut visits = vec![0; columns];
+        for statistics in statistics {
+            visits[statistics.move_] = statistics.visits.min(u32::MAX as u64) as u32;
+        }
+        if visits.iter().all(|&visits| visits == 0) {
+            visits[move_] = 1;
+        }
+        records.push(Record {
+            game: index,
+            ply: records.len() as u32,
+            to_move: game.current_player(),
+            board: (0..columns)
+                .flat_map(|column| (0..rows).map(move |row| (column, row)))
+                .map(|(column, row)| game.state().get(column, row).0)
+                .collect(),
+            visits,
+            move_,
+            result: 0,
+        });
+        game.play(move_).expect("engines only play valid moves");
+    }
+
+    let winner = game.winner();
+    for record in &mut records {
+        record.result = match winner {
+            None => 0,
+            Some(winner) if winner == record.to_move => 1,
+            Some(_) => -1,
+        };
+    }
+    records
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitState, MonteCarloPlayer, Allocation};
+
+    struct LeftmostPlayer;
+
+    impl PlayerTrait for LeftmostPlayer {
+        type Game = ConnectFour<BitState>;
+
+        fn make_move(&self, game: &ConnectFour<BitState>) -> usize {
+            game.valid_moves()[0]
+        }
+    }
+
+    #[test]
+    fn records_every_position_of_a_game() {
+        let game = ConnectFour::<BitState>::new(4, 4).unwrap();
+        let searching = MonteCarloPlayer::with_budget(40, Allocation::Uniform);
+        let records = record_game(3, game, &LeftmostPlayer, &searching);
+        assert!(records.len() >= 7);
+        for (ply, record) in records.iter().enumerate() {
+            assert_eq!(record.game, 3);
+            assert_eq!(record.ply, ply as u32);
+            assert_eq!(record.to_move, Player(ply as u8 % 2 + 1));
+            assert_eq!(record.board.iter().filter(|&&cell| cell != 0).count(), ply);
+            assert!(record.visits[record.move_] > 0);
+        }
+        assert_eq!(records[0].visits, vec![1, 0, 0, 0]);
+        assert_eq!(records[1].visits, vec![40; 4]);
+        let last = records.last().unwrap();
+        assert!(last.result >= 0);
+        assert!(records.windows(2).all(|pair| pair[0].result == -pair[1].result));
+    }
+
+    #[test]
+    fn records_can_be_written() {
+        let record = Record {
+            game: 1,
+            ply: 2,
+            to_move: Player(1),
+            board: vec![0, 2, 0, 1],
+            visits: vec![5, 7],
+            move_: 1,
+            result: -1,
+        };
+        let mut csv = vec![];
+        record.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "1,2,1,0201,5 7,1,-1\n");
+
+        let mut binary = vec![];
+        write_binary_header(&mut binary, 2, 2).unwrap();
+        record.write_binary(&mut binary).unwrap();
+        assert_eq!(binary.len(), 12 + 4 + 4 + 1 + 4 + 2 * 4 + 1 + 1);
+        assert_eq!(binary[binary.len() - 1], 0xff);
+    }
+}
@@ -0,0 +1,79 @@
+//! Strength differences between two players from the results of games between them.
+
+/// The 95 % quantile of the standard normal distribution, for two-sided confidence intervals.
+const Z_95: f64 = 1.959_964;
+
+
+/// Results from the point of view of one player.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Results {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Results {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The points per game, counting draws as half a win.
+    pub fn score(&self) -> Option<f64> {
+        match self.games() {
+            0 => None,
+            games => Some((self.wins as f64 + 0.5 * self.draws as f64) / games as f64),
+        }
+    }
+
+    /// The Elo difference implied by the score and the half width of its 95 % confidence
+    /// interval, which is infinite if the interval reaches a score of 0 or 1. There is no
+    /// estimate if one player has won every game.
+    pub fn elo_difference(&self) -> Option<(f64, f64)> {
+        let score = self.score().filter(|&score| score > 0. && score < 1.)?;
+        let games = self.games() as f64;
+        let variance = (self.wins as f64 * (1. - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2)) / games;
+        let error = Z_95 * (variance / games).sqrt();
+        let (low, high) = (score - error, score + error);
+        let margin = if low > 0. && high < 1. {
+            (elo_difference(high) - elo_difference(low)) / 2.
+        }
+        else {
+            f64::INFINITY
+        };
+        Some((elo_difference(score), margin))
+    }
+}
+
+/// The Elo difference at which the stronger player is expected to score `score` points per
+/// game.
+pub fn elo_difference(score: f64) -> f64 {
+    -400. * (1. / score - 1.).log10()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn even_results_mean_equal_strength() {
+        assert_eq!(elo_difference(0.5), 0.);
+        assert!((elo_difference(0.75) - 190.85).abs() < 0.01);
+        assert!((elo_difference(0.25) + 190.85).abs() < 0.01);
+        let (difference, margin) = Results { wins: 40, draws: 20, losses: 40 }.elo_difference().unwrap();
+        assert_eq!(difference, 0.);
+        assert!(margin > 50. && margin < 70., "{}", margin);
+    }
+
+    #[test]
+    fn margins_shrink_with_more_games() {
+        let (_, few) = Results { wins: 6, draws: 2, losses: 2 }.elo_difference().unwrap();
+        let (_, many) = Results { wins: 600, draws: 200, losses: 200 }.elo_difference().unwrap();
+        assert!(many < few / 5.);
+        assert_eq!(Results { wins: 1, draws: 0, losses: 1 }.elo_difference().unwrap().1, f64::INFINITY);
+        assert_eq!(Results { wins: 3, draws: 0, losses: 0 }.elo_difference(), None);
+        assert_eq!(Results::default().score(), None);
+    }
+}
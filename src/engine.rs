@@ -0,0 +1,376 @@
+//! A line based text protocol for driving a player from another program, in the spirit of UCI
+//! and GTP.
+//!
+//! Commands are read one per line, columns are numbered from 0:
+//!
+//! * `newgame COLUMNS ROWS [WIN_LENGTH]` starts a new game on an empty board. If the board is
+//!   not supported, or the player cannot play on it, the previous game is kept.
+//! * `position [startpos] [moves MOVES]` sets up the position after playing `MOVES` on an empty
+//!   board, either as separate numbers (`4 4 5 3`) or, on boards with at most 10 columns, as
+//!   digits (`4453`).
+//! * `go [movetime MILLISECONDS]` searches the current position and answers with an `info`
+//!   line and `bestmove COLUMN`.
+//! * `analyse [movetime MILLISECONDS]` searches like `go`, but first reports every searched
+//!   move in an `info move COLUMN visits N value V` line. Players that estimate confidence
+//!   intervals add an `info report move COLUMN playouts N wins R draws R losses R value R`
+//!   line per move, where every rate `R` is the estimate followed by the bounds of its
+//!   interval, and `info report separated yes|no`, which tells whether the best move is
+//!   statistically separated from the second best.
+//! * `stop` ends a running search early. The answer is sent as if the search had finished.
+//! * `isready` is answered with `readyok` once all earlier commands have been handled.
+//! * `quit` ends the session, as does the end of the input. Neither interrupts a running
+//!   search, so that scripts can pipe a whole session into the engine.
+//!
+//! `info` lines consist of `depth D visits N`, followed by `value V` if the player estimates
+//! the value of its move, and `pv MOVES` with the principal variation. Invalid commands are
+//! answered with `error MESSAGE`.
+
+use std::io;
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ai_player::Rate;
+use crate::{ConnectFour, Game, PlayerTrait, Report, SearchInfo, State};
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    NewGame { columns: usize, rows: usize, win_length: usize },
+    Position(Vec<usize>),
+    Go { movetime: Option<Duration>, analyse: bool },
+    Stop,
+    IsReady,
+    Quit,
+}
+
+impl Command {
+    /// Parses `line` for a board with `columns` columns, which decides whether a single number
+    /// of several digits is one move or a move per digit.
+    pub fn parse(line: &str, columns: usize) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or("empty command")?;
+        let arguments: Vec<_> = words.collect();
+        let number = |word: &str| word.parse::<usize>().map_err(|_| format!("invalid number `{}`", word));
+        match (command, &arguments[..]) {
+            ("newgame", [columns, rows]) => {
+                Ok(Command::NewGame { columns: number(columns)?, rows: number(rows)?, win_length: 4 })
+            }
+            ("newgame", [columns, rows, win_length]) => {
+                Ok(Command::NewGame { columns: number(columns)?, rows: number(rows)?, win_length: number(win_length)? })
+            }
+            ("position", arguments) => {
+                let arguments = arguments.strip_prefix(&["startpos"]).unwrap_or(arguments);
+                match arguments {
+                    [] => Ok(Command::Position(vec![])),
+                    ["moves", moves @ ..] => {
+                        let moves = match moves {
+                            [digits] if digits.len() > 1 && columns <= 10 => digits.chars()
+                                .map(|digit| digit.to_digit(10).map(|digit| digit as usize)
+                                    .ok_or_else(|| format!("invalid move `{}`", digit)))
+                                .collect::<Result<_, _>>()?,
+                            moves => moves.iter().map(|move_| number(move_)).collect::<Result<_, _>>()?,
+                        };
+                        Ok(Command::Position(moves))
+                    }
+                    _ => Err(format!("invalid position `{}`", arguments.join(" "))),
+                }
+            }
+            ("go" | "analyse", arguments) => {
+                let movetime = match arguments {
+                    [] => None,
+                    ["movetime", milliseconds] => Some(Duration::from_millis(number(milliseconds)? as u64)),
+                    _ => return Err(format!("invalid search limits `{}`", arguments.join(" "))),
+                };
+                Ok(Command::Go { movetime, analyse: command == "analyse" })
+            }
+            ("stop", []) => Ok(Command::Stop),
+            ("isready", []) => Ok(Command::IsReady),
+            ("quit", []) => Ok(Command::Quit),
+            _ => Err(format!("unknown command `{}`", line.trim())),
+        }
+    }
+}
+
+
+fn format_info<M: std::fmt::Display>(info: &SearchInfo<M>) -> String {
+    let mut line = format!("info depth {} visits {}", info.depth, info.visits);
+    if let Some(value) = info.value {
+        line += &format!(" value {:.3}", value);
+    }
+    if !info.principal_variation.is_empty() {
+        let moves: Vec<_> = info.principal_variation.iter().map(|move_| move_.to_string()).collect();
+        line += &format!(" pv {}", moves.join(" "));
+    }
+    line
+}
+
+fn format_report<M: std::fmt::Display>(report: &Report<M>) -> Vec<String> {
+    let rate = |rate: &Rate| format!("{:.3} {:.3} {:.3}", rate.estimate, rate.lower, rate.upper);
+    report.moves.iter()
+        .map(|report| format!(
+            "info report move {} playouts {} wins {} draws {} losses {} value {}",
+            report.move_, report.outcomes.playouts(), rate(&report.wins), rate(&report.draws), rate(&report.losses), rate(&report.value),
+        ))
+        .chain(Some(format!("info report separated {}", if report.is_separated() { "yes" } else { "no" })))
+        .collect()
+}
+
+
+/// Speaks the protocol on `input` and `output` until `quit` or the end of the input.
+/// `new_player` creates the player for every new game, or explains why it cannot play on its
+/// board. Games start as `game` until the first `newgame` command, which fails for board sizes
+/// and win lengths that `S` or the player do not support. Fails with `InvalidInput` if there
+/// is no player for `game`.
+pub fn run<S, R, W>(
+    game: ConnectFour<S>,
+    new_player: impl Fn(&ConnectFour<S>) -> Result<Box<dyn PlayerTrait<Game = ConnectFour<S>>>, String>,
+    input: R,
+    output: &mut W,
+) -> io::Result<()>
+where
+    S: State,
+    R: BufRead + Send + 'static,
+    W: Write,
+{
+    // `stop` has to be noticed while a search is running, so the input is read on its own
+    // thread. Every line is sent along with the number of `stop` commands read before it, and a
+    // search is stopped once more `stop` commands have been read than before its `go`.
+    let stops = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    {
+        let stops = stops.clone();
+        thread::spawn(move || {
+            for line in input.lines() {
+                let Ok(line) = line else { break };
+                let stops_before = stops.load(Ordering::SeqCst);
+                if line.trim() == "stop" {
+                    stops.fetch_add(1, Ordering::SeqCst);
+                }
+                if sender.send((line, stops_before)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    let mut start = game.clone();
+    let mut position = game;
+    let mut player = new_player(&start).map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    while let Ok((line, stops_before)) = receiver.recv() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let command = match Command::parse(&line, start.size().0) {
+            Ok(command) => command,
+            Err(error) => {
+                writeln!(output, "error {}", error)?;
+                output.flush()?;
+                continue;
+            }
+        };
+        match command {
+            Command::NewGame { columns, rows, win_length } => {
+                match ConnectFour::with_win_length(columns, rows, win_length) {
+                    Ok(game) => match new_player(&game) {
+                        Ok(new) => {
+                            start = game;
+                            position = start.clone();
+                            player = new;
+                        }
+                        Err(error) => writeln!(output, "error {}", error)?,
+                    },
+                    Err(()) => writeln!(output, "error unsupported board {}x{} with win length {}", columns, rows, win_length)?,
+                }
+            }
+            Command::Position(moves) => {
+                let mut game = start.clone();
+                let mut valid = true;
+                for &move_ in &moves {
+                    if game.has_ended() || game.play(move_).is_err() {
+                        writeln!(output, "error invalid move {}", move_)?;
+                        valid = false;
+                        break;
+                    }
+                }
+                if valid {
+                    position = game;
+                }
+            }
+            Command::Go { movetime, analyse } => {
+                if position.has_ended() {
+                    writeln!(output, "error the game has ended")?;
+                }
+                else {
+                    let deadline = movetime.map(|movetime| Instant::now() + movetime);
+                    let stop = || {
+                        stops.load(Ordering::SeqCst) > stops_before
+                            || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+                    };
+                    let ((move_, statistics), info) = player.think(&position, &stop);
+                    if analyse {
+                        for statistics in &statistics {
+                            writeln!(output, "info move {} visits {} value {:.3}", statistics.move_, statistics.visits, statistics.value)?;
+                        }
+                        for line in info.report.iter().flat_map(format_report) {
+                            writeln!(output, "{}", line)?;
+                        }
+                    }
+                    writeln!(output, "{}", format_info(&info))?;
+                    writeln!(output, "bestmove {}", move_)?;
+                }
+            }
+            // Stopping is handled while reading, there is nothing to stop otherwise.
+            Command::Stop => {}
+            Command::IsReady => writeln!(output, "readyok")?,
+            Command::Quit => break,
+        }
+        output.flush()?;
+    }
+    output.flush()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_player::DifficultySettings;
+    use crate::{
+        Allocation, BitState, Difficulty, DifficultyPlayer, GraphSearchPlayer, MonteCarloPlayer, SearchConfig, TreeSearchPlayer,
+    };
+
+    type BoxedPlayer = Box<dyn PlayerTrait<Game = ConnectFour<BitState>>>;
+
+    fn session(input: &str, simulations: usize) -> Vec<String> {
+        session_with(input, move || Box::new(TreeSearchPlayer::with_config(SearchConfig { simulations, ..SearchConfig::default() })))
+    }
+
+    fn session_with(input: &str, player: impl Fn() -> BoxedPlayer) -> Vec<String> {
+        let game = ConnectFour::<BitState>::new(7, 6).unwrap();
+        let new_player = move |_: &ConnectFour<BitState>| Ok(player());
+        let mut output = vec![];
+        run(game, new_player, io::Cursor::new(input.to_string()), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parses_commands() {
+        let parse = |line| Command::parse(line, 7);
+        assert_eq!(parse("newgame 7 6"), Ok(Command::NewGame { columns: 7, rows: 6, win_length: 4 }));
+        assert_eq!(parse("position moves 4453"), Ok(Command::Position(vec![4, 4, 5, 3])));
+        assert_eq!(parse("position startpos moves 10 2"), Ok(Command::Position(vec![10, 2])));
+        assert_eq!(parse("position"), Ok(Command::Position(vec![])));
+        assert_eq!(
+            parse("go movetime 1000"),
+            Ok(Command::Go { movetime: Some(Duration::from_secs(1)), analyse: false }),
+        );
+        assert_eq!(parse("analyse"), Ok(Command::Go { movetime: None, analyse: true }));
+        assert!(parse("go fast").is_err());
+        assert!(parse("castle").is_err());
+    }
+
+    #[test]
+    fn digits_are_only_split_on_narrow_boards() {
+        assert_eq!(Command::parse("position moves 10", 10), Ok(Command::Position(vec![1, 0])));
+        assert_eq!(Command::parse("position moves 10", 11), Ok(Command::Position(vec![10])));
+        assert_eq!(Command::parse("position moves 1 0", 11), Ok(Command::Position(vec![1, 0])));
+    }
+
+    #[test]
+    fn answers_go_with_info_and_best_move() {
+        let lines = session("isready\nposition moves 060606\ngo\nposition moves 9\nquit\ngo\n", 300);
+        assert_eq!(lines[0], "readyok");
+        assert!(lines[1].starts_with("info depth 1 visits 0 pv 0"), "{}", lines[1]);
+        assert_eq!(lines[2], "bestmove 0");
+        assert_eq!(lines[3], "error invalid move 9");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn players_may_reject_new_boards() {
+        let game = ConnectFour::<BitState>::new(7, 6).unwrap();
+        let new_player = |game: &ConnectFour<BitState>| -> Result<BoxedPlayer, String> {
+            if game.size() == (7, 6) {
+                Ok(Box::new(TreeSearchPlayer::with_config(SearchConfig { simulations: 100, ..SearchConfig::default() })))
+            }
+            else {
+                Err("the network was built for a 7x6 board".to_string())
+            }
+        };
+        let input = "position moves 060606
+newgame 6 5
+go
+newgame 7 6
+position moves 06060
+go
+";
+        let mut output = vec![];
+        run(game.clone(), new_player, io::Cursor::new(input.to_string()), &mut output).unwrap();
+        let lines: Vec<_> = String::from_utf8(output).unwrap().lines().map(str::to_string).collect();
+        // The previous game, including its position, is kept.
+        assert_eq!(lines[0], "error the network was built for a 7x6 board");
+        assert_eq!(lines[2], "bestmove 0");
+        assert_eq!(lines[4], "bestmove 0");
+
+        let rejecting = |_: &ConnectFour<BitState>| -> Result<BoxedPlayer, String> { Err("no".to_string()) };
+        let error = run(game, rejecting, io::Cursor::new(String::new()), &mut vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn stop_ends_the_search_early() {
+        let start = Instant::now();
+        let lines = session("analyse\nstop\n", usize::MAX);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(lines.len(), 7 + 2);
+        assert!(lines[7].starts_with("info depth"));
+        assert!(lines[8].starts_with("bestmove "));
+    }
+
+    #[test]
+    fn all_searching_players_can_be_stopped() {
+        let players: Vec<Box<dyn Fn() -> BoxedPlayer>> = vec![
+            Box::new(|| Box::new(MonteCarloPlayer::with_budget(1 << 40, Allocation::SuccessiveHalving))),
+            Box::new(|| {
+                let settings = DifficultySettings { simulations: usize::MAX, ..Difficulty::Expert.settings() };
+                Box::new(DifficultyPlayer::with_settings(settings))
+            }),
+            // Solving the empty board takes far longer than the test allows.
+            Box::new(|| {
+                let settings = DifficultySettings { simulations: usize::MAX, solve_plies: usize::MAX, ..Difficulty::Perfect.settings() };
+                Box::new(DifficultyPlayer::with_settings(settings))
+            }),
+            Box::new(|| Box::new(GraphSearchPlayer::with_config(SearchConfig { simulations: usize::MAX, ..SearchConfig::default() }))),
+        ];
+        for player in players {
+            let start = Instant::now();
+            let lines = session_with("go movetime 200\n", &player);
+            assert!(start.elapsed() >= Duration::from_millis(200));
+            assert!(start.elapsed() < Duration::from_secs(10));
+            assert!(lines[1].starts_with("bestmove "), "{:?}", lines);
+
+            let start = Instant::now();
+            let lines = session_with("go\nstop\n", &player);
+            assert!(start.elapsed() < Duration::from_secs(10));
+            assert!(lines[1].starts_with("bestmove "), "{:?}", lines);
+        }
+    }
+
+    #[test]
+    fn analyse_reports_confidence_intervals() {
+        let lines = session_with("position moves 060606\nanalyse\n", || Box::new(MonteCarloPlayer::with_budget(100, Allocation::Uniform)));
+        // The immediate win is taken without a search, so there is nothing to report.
+        assert!(lines.iter().all(|line| !line.starts_with("info report")), "{:?}", lines);
+
+        let lines = session_with("position moves 3\nanalyse\n", || Box::new(MonteCarloPlayer::with_budget(100, Allocation::Uniform)));
+        let reports: Vec<_> = lines.iter().filter(|line| line.starts_with("info report move ")).collect();
+        assert_eq!(reports.len(), 7, "{:?}", lines);
+        assert!(reports[0].contains(" playouts 100 wins "), "{}", reports[0]);
+        assert_eq!(reports[0].split_whitespace().count(), 4 + 2 + 4 * 4);
+        assert!(lines.iter().any(|line| line == "info report separated yes" || line == "info report separated no"));
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+    }
+}
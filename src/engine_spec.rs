@@ -0,0 +1,252 @@
+//! Engines as given on command lines, shared by the game, `selfplay` and `match`.
+//!
+//! An engine is written as its kind, optionally followed by settings separated by colons:
+//!
+//! * `montecarlo[:SIMULATIONS]` is a flat Monte Carlo search.
+//! * `mcts[:POLICY][:SIMULATIONS]` is a Monte Carlo tree search, where `POLICY` is the
+//!   selection policy: `ucb1` (the default), `ucb1-tuned`, `thompson` or `puct`.
+//! * `graph[:SIMULATIONS]` is a Monte Carlo tree search that shares the statistics of
+//!   transpositions, see `SearchGraph`.
+//! * `solver[:SECONDS]` is an alpha-beta search with a time limit per move.
+//! * `network:FILE` is a tree search guided by the network saved in `FILE`.
+//! * `difficulty:LEVEL`, or just `LEVEL`, is a `DifficultyPlayer`.
+//!
+//! `monte-carlo` and `tree-search` are accepted as other names for `montecarlo` and `mcts`.
+//! Engines without their own number of simulations or time limit use the defaults passed to
+//! `EngineSpec::player`.
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::ai_player::{self, Neutral, Puct, Thompson, Ucb1, Ucb1Tuned};
+use crate::connect_four::eval::Evaluator;
+use crate::{
+    Allocation, BitState, ConnectFour, Difficulty, DifficultyPlayer, Game, GraphSearchPlayer, MinimaxPlayer,
+    MonteCarloPlayer, Network, NetworkPlayer, PlayerTrait, SearchConfig, State, TreeSearchPlayer, VecState,
+};
+
+
+/// The board representations engines can play on.
+pub trait Board: State + 'static {
+    /// An alpha-beta search on `game` with the best evaluation the board supports.
+    fn solver(game: &ConnectFour<Self>, time_limit: Duration) -> Box<dyn PlayerTrait<Game=ConnectFour<Self>>>;
+}
+
+impl Board for BitState {
+    fn solver(game: &ConnectFour<BitState>, time_limit: Duration) -> Box<dyn PlayerTrait<Game=ConnectFour<BitState>>> {
+        let evaluator = Evaluator::for_state(game.state());
+        Box::new(MinimaxPlayer::new(evaluator).with_time_limit(Some(time_limit)))
+    }
+}
+
+impl Board for VecState {
+    /// There is no heuristic for other win lengths, so the solver only sees wins and losses.
+    fn solver(_game: &ConnectFour<VecState>, time_limit: Duration) -> Box<dyn PlayerTrait<Game=ConnectFour<VecState>>> {
+        Box::new(MinimaxPlayer::new(Neutral).with_time_limit(Some(time_limit)))
+    }
+}
+
+
+/// The selection policies of the tree search, see `SelectionPolicy`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Selection {
+    Ucb1,
+    Ucb1Tuned,
+    Thompson,
+    Puct,
+}
+
+impl Selection {
+    pub const ALL: [Selection; 4] = [Selection::Ucb1, Selection::Ucb1Tuned, Selection::Thompson, Selection::Puct];
+
+    fn name(self) -> &'static str {
+        match self {
+            Selection::Ucb1 => "ucb1",
+            Selection::Ucb1Tuned => "ucb1-tuned",
+            Selection::Thompson => "thompson",
+            Selection::Puct => "puct",
+        }
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Selection, String> {
+        Selection::ALL.iter()
+            .cloned()
+            .find(|selection| selection.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Selection::ALL.iter().map(|selection| selection.name()).collect();
+                format!("unknown selection policy `{}`, expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EngineSpec {
+    MonteCarlo { simulations: Option<usize> },
+    TreeSearch { selection: Selection, simulations: Option<usize> },
+    Graph { simulations: Option<usize> },
+    Solver { time_limit: Option<Duration> },
+    /// The network is loaded while parsing, so that it is shared by all players.
+    Network { network: Arc<Network>, simulations: Option<usize> },
+    Difficulty(Difficulty),
+}
+
+fn parse_simulations(setting: &str) -> Result<usize, String> {
+    setting.parse().map_err(|_| format!("invalid simulations `{}`", setting))
+}
+
+fn parse_seconds(setting: &str) -> Result<Duration, String> {
+    setting.parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid number of seconds `{}`", setting))
+}
+
+impl FromStr for EngineSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EngineSpec, String> {
+        let (kind, setting) = match s.split_once(':') {
+            Some((kind, setting)) => (kind, Some(setting)),
+            None => (s, None),
+        };
+        match (kind, setting) {
+            ("montecarlo" | "monte-carlo", _) => {
+                Ok(EngineSpec::MonteCarlo { simulations: setting.map(parse_simulations).transpose()? })
+            }
+            ("mcts" | "tree-search", None) => Ok(EngineSpec::TreeSearch { selection: Selection::Ucb1, simulations: None }),
+            ("mcts" | "tree-search", Some(settings)) => {
+                let (selection, simulations) = match settings.split_once(':') {
+                    Some((selection, simulations)) => (selection.parse()?, Some(parse_simulations(simulations)?)),
+                    None if settings.starts_with(|c: char| c.is_ascii_digit()) => (Selection::Ucb1, Some(parse_simulations(settings)?)),
+                    None => (settings.parse()?, None),
+                };
+                Ok(EngineSpec::TreeSearch { selection, simulations })
+            }
+            ("graph", _) => Ok(EngineSpec::Graph { simulations: setting.map(parse_simulations).transpose()? }),
+            ("solver", _) => Ok(EngineSpec::Solver { time_limit: setting.map(parse_seconds).transpose()? }),
+            ("network", Some(path)) => {
+                let file = File::open(path).map_err(|error| format!("cannot open `{}`: {}", path, error))?;
+                let network = Network::load(&mut BufReader::new(file))
+                    .map_err(|error| format!("cannot load `{}`: {:?}", path, error))?;
+                Ok(EngineSpec::Network { network: Arc::new(network), simulations: None })
+            }
+            ("difficulty", Some(level)) => Ok(EngineSpec::Difficulty(level.parse()?)),
+            (_, None) => s.parse()
+                .map(EngineSpec::Difficulty)
+                .map_err(|_| format!("unknown engine `{}`", s)),
+            _ => Err(format!("unknown engine `{}`", s)),
+        }
+    }
+}
+
+impl EngineSpec {
+    /// The engine with its number of simulations replaced by `simulations`, if it counts
+    /// them and `simulations` is given.
+    pub fn with_simulations(self, simulations: Option<usize>) -> EngineSpec {
+        match (self, simulations) {
+            (EngineSpec::MonteCarlo { .. }, Some(_)) => EngineSpec::MonteCarlo { simulations },
+            (EngineSpec::TreeSearch { selection, .. }, Some(_)) => EngineSpec::TreeSearch { selection, simulations },
+            (EngineSpec::Graph { .. }, Some(_)) => EngineSpec::Graph { simulations },
+            (EngineSpec::Network { network, .. }, Some(_)) => EngineSpec::Network { network, simulations },
+            (spec, _) => spec,
+        }
+    }
+
+    /// A player for games like `game`. `simulations` and `time_limit` apply to engines that
+    /// were not given their own. Fails if a network was built for another board size.
+    pub fn player<S: Board>(
+        &self,
+        game: &ConnectFour<S>,
+        simulations: usize,
+        time_limit: Duration,
+    ) -> Result<Box<dyn PlayerTrait<Game=ConnectFour<S>>>, String> {
+        Ok(match *self {
+            EngineSpec::MonteCarlo { simulations: own_simulations } => {
+                let simulations = own_simulations.unwrap_or(simulations);
+                Box::new(MonteCarloPlayer::with_budget(simulations, Allocation::SuccessiveHalving))
+            }
+            EngineSpec::TreeSearch { selection, simulations: own_simulations } => {
+                let config = SearchConfig { simulations: own_simulations.unwrap_or(simulations), ..SearchConfig::default() };
+                tree_search(selection, config)
+            }
+            EngineSpec::Graph { simulations: own_simulations } => {
+                let config = SearchConfig { simulations: own_simulations.unwrap_or(simulations), ..SearchConfig::default() };
+                Box::new(GraphSearchPlayer::with_config(config))
+            }
+            EngineSpec::Solver { time_limit: own_time_limit } => S::solver(game, own_time_limit.unwrap_or(time_limit)),
+            EngineSpec::Network { ref network, simulations: own_simulations } => {
+                let config = SearchConfig { simulations: own_simulations.unwrap_or(simulations), ..ai_player::NETWORK_SEARCH_CONFIG };
+                Box::new(NetworkPlayer::with_config(network.clone(), game, config)?)
+            }
+            EngineSpec::Difficulty(difficulty) => Box::new(DifficultyPlayer::new(difficulty)),
+        })
+    }
+}
+
+fn tree_search<G: Game + 'static>(selection: Selection, config: SearchConfig) -> Box<dyn PlayerTrait<Game=G>> {
+    match selection {
+        Selection::Ucb1 => Box::new(TreeSearchPlayer::with_policy(config, Ucb1)),
+        Selection::Ucb1Tuned => Box::new(TreeSearchPlayer::with_policy(config, Ucb1Tuned)),
+        Selection::Thompson => Box::new(TreeSearchPlayer::with_policy(config, Thompson)),
+        Selection::Puct => Box::new(TreeSearchPlayer::with_policy(config, Puct)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engines_can_be_parsed() {
+        let parse = |s: &str| s.parse::<EngineSpec>();
+        assert_eq!(parse("montecarlo:500"), Ok(EngineSpec::MonteCarlo { simulations: Some(500) }));
+        assert_eq!(parse("monte-carlo"), Ok(EngineSpec::MonteCarlo { simulations: None }));
+        assert_eq!(parse("mcts"), Ok(EngineSpec::TreeSearch { selection: Selection::Ucb1, simulations: None }));
+        assert_eq!(parse("tree-search:800"), Ok(EngineSpec::TreeSearch { selection: Selection::Ucb1, simulations: Some(800) }));
+        assert_eq!(parse("mcts:thompson"), Ok(EngineSpec::TreeSearch { selection: Selection::Thompson, simulations: None }));
+        assert_eq!(parse("mcts:ucb1-tuned:2000"), Ok(EngineSpec::TreeSearch { selection: Selection::Ucb1Tuned, simulations: Some(2000) }));
+        assert_eq!(parse("graph:300"), Ok(EngineSpec::Graph { simulations: Some(300) }));
+        assert_eq!(parse("solver:0.5"), Ok(EngineSpec::Solver { time_limit: Some(Duration::from_millis(500)) }));
+        assert_eq!(parse("difficulty:hard"), Ok(EngineSpec::Difficulty(Difficulty::Hard)));
+        assert_eq!(parse("expert"), Ok(EngineSpec::Difficulty(Difficulty::Expert)));
+        for invalid in ["mcts:greedy", "graph:all", "mcts:puct:many", "montecarlo:-1", "solver:forever", "network", "human", "hard:1"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+        assert!(parse("network:/nonexistent").unwrap_err().starts_with("cannot open"));
+    }
+
+    #[test]
+    fn engines_create_their_players() {
+        let game = ConnectFour::<BitState>::new(7, 6).unwrap();
+        for &selection in Selection::ALL.iter() {
+            let spec = EngineSpec::TreeSearch { selection, simulations: Some(50) };
+            let player = spec.player(&game, 0, Duration::from_secs(1)).unwrap();
+            assert!(game.valid_moves().contains(&player.make_move(&game)));
+        }
+        let player = EngineSpec::Graph { simulations: Some(50) }.player(&game, 0, Duration::from_secs(1)).unwrap();
+        assert!(game.valid_moves().contains(&player.make_move(&game)));
+        let spec = EngineSpec::MonteCarlo { simulations: None }.with_simulations(Some(20));
+        assert_eq!(spec, EngineSpec::MonteCarlo { simulations: Some(20) });
+        assert_eq!(EngineSpec::Difficulty(Difficulty::Easy).with_simulations(Some(20)), EngineSpec::Difficulty(Difficulty::Easy));
+
+        let network = Arc::new(Network::new(6, 5, &[4], &mut rand::thread_rng()));
+        let spec = EngineSpec::Network { network, simulations: None };
+        assert!(spec.player(&game, 10, Duration::from_secs(1)).is_err());
+    }
+}
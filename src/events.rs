@@ -0,0 +1,236 @@
+//! A live record of a game as one JSON object per line, for spectators, dashboards and
+//! archives. Every line has an `event` member, the others depend on the event:
+//!
+//! * `start`: `columns`, `rows`, `win_length`, the `position` (see `Notation`), the `moves`
+//!   played so far, e. g. in a loaded game, and the player `to_move`.
+//! * `move`: the `player`, the `move`, the `thinking_time` in seconds and the resulting
+//!   `position`. Players whose searches are observed also report the `value` of the move for
+//!   them, the search `depth`, the number of `visits` and the principal variation `pv`.
+//! * `invalid_move`: the `player`, the `move` and the `reason`.
+//! * `undo`: the `player`, the number of `plies` taken back and the resulting `position`.
+//! * `save`: the `player`, the `path` and an `error` if saving failed.
+//! * `draw_offer`: the `player` and whether the offer was `accepted`.
+//! * `end`: the `reason`, one of `win`, `draw`, `agreement`, `resignation` or `quit`, the
+//!   `winner` if there is one, and the `player` who resigned or quit.
+//!
+//! Players are numbered 1 and 2, moves are columns numbered from 0.
+
+use std::io::prelude::*;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::json::Json;
+use crate::{
+    Action, Analysis, ConnectFour, Event, Game, Moves, Notation, Player, PlayerTrait, SearchInfo, State, Thought, Winner,
+};
+
+
+/// How a player came to their last move, until the move is written.
+struct Thinking {
+    player: Player,
+    move_: usize,
+    time: Duration,
+    info: Option<SearchInfo<usize>>,
+}
+
+struct Shared {
+    output: Mutex<Box<dyn Write + Send>>,
+    thinking: Mutex<Option<Thinking>>,
+}
+
+impl Shared {
+    /// The game goes on without its spectators, so errors are ignored.
+    fn write(&self, members: Vec<(&str, Json)>) {
+        let mut output = self.output.lock().unwrap();
+        let _ = writeln!(output, "{}", Json::object(members)).and_then(|_| output.flush());
+    }
+}
+
+fn player(Player(player): Player) -> Json {
+    Json::from(player as usize)
+}
+
+
+/// Writes the events of a game to a file, a pipe, a socket or any other stream. Clones write
+/// to the same stream.
+pub struct EventStream<S: State> {
+    shared: Arc<Shared>,
+    _state: PhantomData<S>,
+}
+
+impl<S: State> Clone for EventStream<S> {
+    fn clone(&self) -> EventStream<S> {
+        EventStream { shared: self.shared.clone(), _state: PhantomData }
+    }
+}
+
+impl<S: State> EventStream<S> {
+    pub fn new(output: impl Write + Send + 'static) -> EventStream<S> {
+        let shared = Shared { output: Mutex::new(Box::new(output)), thinking: Mutex::new(None) };
+        EventStream { shared: Arc::new(shared), _state: PhantomData }
+    }
+
+    /// Reports the thinking time and the invalid moves of `player`, which acts as usual.
+    pub fn observe(&self, player: Box<dyn PlayerTrait<Game=ConnectFour<S>>>) -> ObservedPlayer<S> {
+        ObservedPlayer { player, shared: self.shared.clone(), search: false, last_move: Mutex::new(None) }
+    }
+
+    /// Like `observe`, but moves are found with `think`, so that their evaluation is reported
+    /// as well. Only for AI players, humans could not undo or resign anymore.
+    pub fn observe_search(&self, player: Box<dyn PlayerTrait<Game=ConnectFour<S>>>) -> ObservedPlayer<S> {
+        ObservedPlayer { search: true, ..self.observe(player) }
+    }
+
+    /// Writes the `start` line with the position of `moves` before the first event.
+    pub fn start(&self, moves: &Moves<ConnectFour<S>>) {
+        let game = moves.game();
+        let (columns, rows) = game.size();
+        self.shared.write(vec![
+            ("event", Json::from("start")),
+            ("columns", Json::from(columns)),
+            ("rows", Json::from(rows)),
+            ("win_length", Json::from(game.win_length())),
+            ("position", Json::from(game.notation())),
+            ("moves", Json::from(moves.history().to_vec())),
+            ("to_move", player(game.current_player())),
+        ]);
+    }
+
+    /// Writes the lines for `event`, which has led to `game`.
+    pub fn write(&self, event: &Event<ConnectFour<S>>, game: &ConnectFour<S>) {
+        let end = |reason: &str, winner: Option<Player>, by: Option<Player>| {
+            let mut members = vec![("event", Json::from("end")), ("reason", Json::from(reason))];
+            members.push(("winner", winner.map_or(Json::Null, player)));
+            if let Some(by) = by {
+                members.push(("player", player(by)));
+            }
+            self.shared.write(members);
+        };
+        match event {
+            Event::Moved { player: mover, move_, winner, .. } => {
+                let mut members = vec![
+                    ("event", Json::from("move")),
+                    ("player", player(*mover)),
+                    ("move", Json::from(*move_)),
+                ];
+                let thinking = self.shared.thinking.lock().unwrap().take()
+                    .filter(|thinking| thinking.player == *mover && thinking.move_ == *move_);
+                if let Some(thinking) = thinking {
+                    members.push(("thinking_time", Json::from(thinking.time.as_secs_f64())));
+                    if let Some(info) = thinking.info {
+                        members.extend(vec![
+                            ("value", Json::from(info.value)),
+                            ("depth", Json::from(info.depth)),
+                            ("visits", Json::from(info.visits)),
+                            ("pv", Json::from(info.principal_variation)),
+                        ]);
+                    }
+                }
+                members.push(("position", Json::from(game.notation())));
+                self.shared.write(members);
+                match winner {
+                    Winner::Winner(winner) => end("win", Some(*winner), None),
+                    Winner::Draw => end("draw", None, None),
+                    Winner::NotFinishedYet => {}
+                }
+            }
+            Event::Undone { player: by, plies, .. } => self.shared.write(vec![
+                ("event", Json::from("undo")),
+                ("player", player(*by)),
+                ("plies", Json::from(*plies)),
+                ("position", Json::from(game.notation())),
+            ]),
+            Event::Saved { player: by, path, result } => self.shared.write(vec![
+                ("event", Json::from("save")),
+                ("player", player(*by)),
+                ("path", Json::from(path.display().to_string())),
+                ("error", Json::from(result.as_ref().err().map(ToString::to_string))),
+            ]),
+            Event::Resigned(by) => end("resignation", Some(Player(3 - by.0)), Some(*by)),
+            Event::DrawOffered { player: by, accepted } => {
+                self.shared.write(vec![
+                    ("event", Json::from("draw_offer")),
+                    ("player", player(*by)),
+                    ("accepted", Json::from(*accepted)),
+                ]);
+                if *accepted {
+                    end("agreement", None, None);
+                }
+            }
+            Event::Quit(by) => end("quit", None, Some(*by)),
+        }
+    }
+}
+
+
+/// A player whose moves are timed for an `EventStream`, see `EventStream::observe`.
+pub struct ObservedPlayer<S: State> {
+    player: Box<dyn PlayerTrait<Game=ConnectFour<S>>>,
+    shared: Arc<Shared>,
+    search: bool,
+    /// The player and move of the last `act`, to report invalid moves.
+    last_move: Mutex<Option<(Player, usize)>>,
+}
+
+impl<S: State> ObservedPlayer<S> {
+    fn record(&self, game: &ConnectFour<S>, move_: usize, time: Duration, info: Option<SearchInfo<usize>>) {
+        let player = game.current_player();
+        *self.last_move.lock().unwrap() = Some((player, move_));
+        *self.shared.thinking.lock().unwrap() = Some(Thinking { player, move_, time, info });
+    }
+}
+
+impl<S: State> PlayerTrait for ObservedPlayer<S> {
+    type Game = ConnectFour<S>;
+
+    fn make_move(&self, game: &ConnectFour<S>) -> usize {
+        let start = Instant::now();
+        let move_ = self.player.make_move(game);
+        self.record(game, move_, start.elapsed(), None);
+        move_
+    }
+
+    fn analyse(&self, game: &ConnectFour<S>) -> Analysis<usize> {
+        self.player.analyse(game)
+    }
+
+    /// Records the search, so that players wrapping this one are observed as well.
+    fn think(&self, game: &ConnectFour<S>, stop: &dyn Fn() -> bool) -> Thought<usize> {
+        let start = Instant::now();
+        let thought = self.player.think(game, stop);
+        let ((move_, _), info) = &thought;
+        self.record(game, *move_, start.elapsed(), Some(info.clone()));
+        thought
+    }
+
+    fn act(&self, game: &ConnectFour<S>) -> Action<usize> {
+        if self.search {
+            let ((move_, _), _) = self.think(game, &|| false);
+            return Action::Move(move_);
+        }
+        let start = Instant::now();
+        let action = self.player.act(game);
+        if let Action::Move(move_) = action {
+            self.record(game, move_, start.elapsed(), None);
+        }
+        action
+    }
+
+    fn accepts_draw(&self, game: &ConnectFour<S>) -> bool {
+        self.player.accepts_draw(game)
+    }
+
+    fn invalid_move(&self, move_: <Self::Game as Game>::InvalidMove) {
+        if let Some((by, column)) = self.last_move.lock().unwrap().take() {
+            self.shared.write(vec![
+                ("event", Json::from("invalid_move")),
+                ("player", player(by)),
+                ("move", Json::from(column)),
+                ("reason", Json::from(format!("{:?}", move_))),
+            ]);
+        }
+        self.player.invalid_move(move_)
+    }
+}
+
@@ -1,4 +1,7 @@
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use super::PlayerTrait;
@@ -21,53 +24,208 @@ pub trait Game: Send + Sync + Clone {
         self.valid_moves().is_empty() || self.winner().is_some()
     }
 
-    fn iter(self, players: Vec<Box<PlayerTrait<Game=Self>>>) -> Moves<Self> {
+    /// An upper bound on the number of moves until the game ends, if the game knows one.
+    fn remaining_moves(&self) -> Option<usize> {
+        None
+    }
+
+    /// The moves with which the player to move wins immediately. Implementors can override
+    /// this with something faster than trying every move.
+    fn winning_moves(&self) -> Vec<Self::Move> {
+        self.valid_moves()
+            .into_iter()
+            .filter(|&move_| {
+                let mut game = self.clone();
+                matches!(game.play(move_), Ok(Some(_)))
+            })
+            .collect()
+    }
+
+    fn iter(self, players: Vec<Box<dyn PlayerTrait<Game=Self>>>) -> Moves<Self> {
         Moves::new(self, players)
     }
 }
 
 
+/// Games whose positions can be identified by a key, e. g. to detect transpositions.
+pub trait PositionKey: Game {
+    /// Positions that only differ in the order of the moves that led to them must have the same
+    /// key. Different positions should have different keys, but implementations may use hashing
+    /// where an exact encoding is not feasible.
+    fn position_key(&self) -> u64;
+}
+
+
+/// Games whose positions can be written as a single line of text, e. g. to send them to
+/// another program.
+pub trait Notation: Game {
+    /// Must not contain line breaks.
+    fn notation(&self) -> String;
+    fn from_notation(notation: &str) -> Result<Self, String>;
+}
+
+
+/// Perfect knowledge about some positions, for example from an endgame tablebase.
+pub trait Oracle<G: Game>: Send + Sync {
+    /// An optimal move in `game`, or `None` if the position is not covered.
+    fn best_move(&self, game: &G) -> Option<G::Move>;
+}
+
+
+/// A static evaluation of positions.
+pub trait Evaluate<G: Game>: Send + Sync {
+    /// The heuristic value of `game` for `player`, higher values are better for `player`.
+    fn evaluate(&self, game: &G, player: Player) -> i32;
+}
+
+
+/// What a player does on their turn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action<M> {
+    Move(M),
+    /// Takes back moves up to and including the player’s previous move.
+    Undo,
+    /// Saves the moves played so far to a file, as columns separated by spaces.
+    Save(PathBuf),
+    Resign,
+    /// Offers a draw, which ends the game if the opponent accepts it.
+    OfferDraw,
+    /// Ends the game without a result.
+    Quit,
+}
+
+
+/// Something that happened during a game, see `Game::iter`.
+pub enum Event<G: Game> {
+    Moved { state: G::State, player: Player, move_: G::Move, winner: Winner },
+    /// `player` took back `plies` moves. Nothing is taken back if the player has not moved yet.
+    Undone { state: G::State, player: Player, plies: usize },
+    Saved { player: Player, path: PathBuf, result: io::Result<()> },
+    Resigned(Player),
+    DrawOffered { player: Player, accepted: bool },
+    Quit(Player),
+}
+
+
 pub struct Moves<G: Game> {
+    start: G,
     game: G,
-    players: Vec<Box<PlayerTrait<Game=G>>>,
-    current_player_index: usize,
+    players: Vec<Box<dyn PlayerTrait<Game=G>>>,
+    history: Vec<G::Move>,
+    finished: bool,
 }
 
 impl<G: Game> Moves<G> {
-    fn new(game: G, players: Vec<Box<PlayerTrait<Game=G>>>) -> Moves<G> {
+    fn new(game: G, players: Vec<Box<dyn PlayerTrait<Game=G>>>) -> Moves<G> {
         Moves {
-            game: game,
-            players: players,
-            current_player_index: 0,
+            start: game.clone(),
+            game,
+            players,
+            history: vec![],
+            finished: false,
+        }
+    }
+
+    /// Plays `moves` before the players take over, e. g. to continue a saved game. The moves
+    /// are part of the history, so they can be undone and are saved again.
+    pub fn replay(mut self, moves: &[G::Move]) -> Result<Moves<G>, G::InvalidMove> {
+        for &move_ in moves {
+            self.game.play(move_)?;
+            self.history.push(move_);
+        }
+        Ok(self)
+    }
+
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    /// The moves played so far, including replayed moves.
+    pub fn history(&self) -> &[G::Move] {
+        &self.history
+    }
+
+    /// The players take turns in the order in which they were given, starting with the first
+    /// move of the history.
+    fn current_player_index(&self) -> usize {
+        self.history.len() % self.players.len()
+    }
+
+    /// Takes back moves up to and including the last move of the player with the given index.
+    fn undo(&mut self, player_index: usize) -> usize {
+        let num_players = self.players.len();
+        let Some(plies) = (0..self.history.len())
+            .rev()
+            .find(|&ply| ply % num_players == player_index)
+            .map(|ply| self.history.len() - ply)
+        else {
+            return 0;
+        };
+        self.history.truncate(self.history.len() - plies);
+        self.game = self.start.clone();
+        for &move_ in &self.history {
+            self.game.play(move_).unwrap_or_else(|err| panic!("history contains invalid move: {:?}", err));
         }
+        plies
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let moves: Vec<_> = self.history.iter().map(|move_| move_.to_string()).collect();
+        fs::write(path, moves.join(" ") + "\n")
     }
 }
 
 impl<G: Game> Iterator for Moves<G> {
-    type Item = (G::State, Player, G::Move, Winner);
+    type Item = Event<G>;
 
-    #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
-        if self.game.has_ended() || self.game.valid_moves().is_empty() {
+        if self.finished || self.game.has_ended() || self.game.valid_moves().is_empty() {
             return None;
         }
 
         let num_players = self.players.len();
+        let index = self.current_player_index();
         let player = self.game.current_player();
 
         loop {
-            let move_ = self.players[self.current_player_index].make_move(&self.game);
+            let action = self.players[index].act(&self.game);
+            let move_ = match action {
+                Action::Move(move_) => move_,
+                Action::Undo => {
+                    let plies = self.undo(index);
+                    return Some(Event::Undone { state: self.game.state().clone(), player, plies });
+                }
+                Action::Save(path) => {
+                    let result = self.save(&path);
+                    return Some(Event::Saved { player, path, result });
+                }
+                Action::Resign => {
+                    self.finished = true;
+                    return Some(Event::Resigned(player));
+                }
+                Action::OfferDraw => {
+                    let accepted = (1..num_players)
+                        .map(|offset| &self.players[(index + offset) % num_players])
+                        .all(|opponent| opponent.accepts_draw(&self.game));
+                    self.finished = accepted;
+                    return Some(Event::DrawOffered { player, accepted });
+                }
+                Action::Quit => {
+                    self.finished = true;
+                    return Some(Event::Quit(player));
+                }
+            };
             match self.game.play(move_) {
                 Ok(maybe_winner) => {
-                    self.current_player_index = (self.current_player_index + 1) % num_players;
+                    self.history.push(move_);
                     let winner = match maybe_winner {
                         Some(winner) => Winner::Winner(winner),
                         None => if self.game.has_ended() { Winner::Draw } else { Winner::NotFinishedYet }
                     };
-                    return Some((self.game.state().clone(), player, move_, winner));
+                    return Some(Event::Moved { state: self.game.state().clone(), player, move_, winner });
                 },
                 Err(invalid_move) => {
-                    self.players[self.current_player_index].invalid_move(invalid_move);
+                    self.players[index].invalid_move(invalid_move);
                 }
             }
         }
@@ -78,12 +236,10 @@ impl<G: Game> Iterator for Moves<G> {
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Player(pub u8);
 
+/// Plain `X` and `O`, see `connect_four::render` for other renderings.
 impl fmt::Display for Player {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f, "{}\x1B[0m",
-            if self.0 == 1 { "\x1B[44;1mX" } else if self.0 == 2 { "\x1B[41;1mO" } else { " " }
-        )
+        f.write_str(if self.0 == 1 { "X" } else if self.0 == 2 { "O" } else { " " })
     }
 }
 
@@ -94,3 +250,95 @@ pub enum Winner {
     Draw,
     NotFinishedYet,
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{BitState, ConnectFour, State};
+
+    type Connect4 = ConnectFour<BitState>;
+
+    /// Takes its actions from a script and accepts draws as told.
+    struct Scripted {
+        actions: RefCell<Vec<Action<usize>>>,
+        accepts_draw: bool,
+    }
+
+    impl Scripted {
+        fn boxed(actions: Vec<Action<usize>>) -> Box<dyn PlayerTrait<Game=Connect4>> {
+            Box::new(Scripted { actions: RefCell::new(actions), accepts_draw: false })
+        }
+    }
+
+    impl PlayerTrait for Scripted {
+        type Game = Connect4;
+
+        fn make_move(&self, _game: &Connect4) -> usize {
+            unreachable!("scripted players only act")
+        }
+
+        fn act(&self, _game: &Connect4) -> Action<usize> {
+            self.actions.borrow_mut().remove(0)
+        }
+
+        fn accepts_draw(&self, _game: &Connect4) -> bool {
+            self.accepts_draw
+        }
+    }
+
+    fn describe(event: &Event<Connect4>) -> String {
+        match event {
+            Event::Moved { player, move_, .. } => format!("{} moved {}", player.0, move_),
+            Event::Undone { player, plies, .. } => format!("{} undid {}", player.0, plies),
+            Event::Saved { player, result, .. } => format!("{} saved {}", player.0, result.is_ok()),
+            Event::Resigned(player) => format!("{} resigned", player.0),
+            Event::DrawOffered { player, accepted } => format!("{} offered {}", player.0, accepted),
+            Event::Quit(player) => format!("{} quit", player.0),
+        }
+    }
+
+    #[test]
+    fn undo_takes_back_the_players_previous_move() {
+        let first = Scripted::boxed(vec![Action::Undo, Action::Move(0), Action::Move(1), Action::Undo, Action::Move(2), Action::Quit]);
+        let second = Scripted::boxed(vec![Action::Move(5), Action::Move(6), Action::Move(6)]);
+        let mut moves = ConnectFour::new(7, 6).unwrap().iter(vec![first, second]);
+        let events: Vec<_> = moves.by_ref().map(|event| describe(&event)).collect();
+        assert_eq!(events, [
+            "1 undid 0", "1 moved 0", "2 moved 5", "1 moved 1", "2 moved 6", "1 undid 2", "1 moved 2", "2 moved 6",
+            "1 quit",
+        ].map(str::to_string));
+        assert_eq!(moves.history, vec![0, 5, 2, 6]);
+        assert_eq!(moves.game().state().get(2, 5), Player(1));
+        assert_eq!(moves.game().state().get(1, 5), Player(0));
+    }
+
+    #[test]
+    fn games_end_by_resignation_or_agreement() {
+        let first = Scripted::boxed(vec![Action::Move(3), Action::OfferDraw, Action::Resign]);
+        let second = Scripted::boxed(vec![Action::Move(3)]);
+        let events: Vec<_> = ConnectFour::new(7, 6).unwrap().iter(vec![first, second]).map(|event| describe(&event)).collect();
+        assert_eq!(events, ["1 moved 3", "2 moved 3", "1 offered false", "1 resigned"]);
+
+        let first = Scripted::boxed(vec![Action::OfferDraw]);
+        let second = Box::new(Scripted { actions: RefCell::new(vec![]), accepts_draw: true });
+        let events: Vec<_> = ConnectFour::new(7, 6).unwrap().iter(vec![first, second]).map(|event| describe(&event)).collect();
+        assert_eq!(events, ["1 offered true"]);
+    }
+
+    #[test]
+    fn saved_games_can_be_replayed() {
+        let path = std::env::temp_dir().join(format!("mcc4-saved-game-{}.txt", std::process::id()));
+        let first = Scripted::boxed(vec![Action::Move(3), Action::Save(path.clone()), Action::Quit]);
+        let second = Scripted::boxed(vec![Action::Move(4)]);
+        let events: Vec<_> = ConnectFour::new(7, 6).unwrap().iter(vec![first, second]).map(|event| describe(&event)).collect();
+        assert_eq!(events, ["1 moved 3", "2 moved 4", "1 saved true", "1 quit"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "3 4\n");
+        fs::remove_file(&path).unwrap();
+
+        let moves = ConnectFour::<BitState>::new(7, 6).unwrap().iter(vec![]).replay(&[3, 4]).unwrap();
+        assert_eq!(moves.game().state().get(4, 5), Player(2));
+    }
+}
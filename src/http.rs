@@ -0,0 +1,496 @@
+//! A small HTTP interface with JSON bodies, so that web frontends and other tools can use a
+//! player without starting a process per request. Every request is read on its own thread and
+//! handled on the rayon pool.
+//!
+//! Positions are given in one of two ways, with `columns`, `rows` and `win_length` defaulting
+//! to the board the server was started with:
+//!
+//! * `{"position": NOTATION}`, see `Notation`.
+//! * `{"moves": [COLUMN, …], "columns": N, "rows": N, "win_length": N}`, the columns played on
+//!   an empty board.
+//!
+//! Searches accept a budget of `simulations` for players that count playouts and a `movetime`
+//! in milliseconds after which the search is stopped. Both are limited, so that no request
+//! occupies the pool for long: larger budgets are reduced to `MAX_SIMULATIONS`, and every
+//! search is stopped after `MAX_MOVETIME`. The endpoints are:
+//!
+//! * `POST /analyse` answers `{"bestmove", "depth", "visits", "value", "pv", "moves"}`, where
+//!   `moves` holds the `move`, `visits` and `value` of every searched move. Players that
+//!   estimate confidence intervals add `"report": {"separated", "moves"}`, where `moves` holds
+//!   the `move`, `playouts`, `wins`, `draws`, `losses` and `value` of every move, each rate as
+//!   `{"estimate", "lower", "upper"}`.
+//! * `POST /bestmove` answers the same without `moves`.
+//! * `POST /games` creates a game from a position, the empty board by default, and answers
+//!   its state with `201 Created`.
+//! * `GET /games/{id}` answers the state of a game: `{"id", "position", "moves", "to_move",
+//!   "winner", "result", "valid_moves"}`, where `moves` are the moves played since its creation
+//!   and `result` is `ongoing`, `won` or `draw`.
+//! * `POST /games/{id}/moves` plays `{"move": COLUMN}` and answers the new state.
+//! * `DELETE /games/{id}` forgets a game.
+//!
+//! Errors are answered with a status code and `{"error": MESSAGE}`.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ai_player::Rate;
+use crate::json::Json;
+use crate::{ConnectFour, Game, Notation, Player, PlayerTrait, Report, State};
+
+pub const DEFAULT_PORT: u16 = 4005;
+
+/// Requests with larger headers or bodies are rejected.
+const MAX_REQUEST_SIZE: u64 = 1 << 20;
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Searches never get more simulations than this.
+pub const MAX_SIMULATIONS: usize = 10 * crate::ai_player::SIMULATIONS;
+/// Searches are stopped after this long, even if the request allows more time.
+pub const MAX_MOVETIME: Duration = Duration::from_secs(60);
+
+
+/// A status code and the JSON body of a response.
+pub type Response = (u16, Json);
+
+fn error(status: u16, message: impl Into<String>) -> Response {
+    (status, Json::object(vec![("error", Json::String(message.into()))]))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    }
+}
+
+
+/// A game created with `POST /games`.
+struct Entry<S: State> {
+    game: ConnectFour<S>,
+    moves: Vec<usize>,
+}
+
+
+pub struct Server<S: State, F> {
+    game: ConnectFour<S>,
+    new_player: F,
+    games: Mutex<HashMap<u64, Entry<S>>>,
+    next_id: AtomicU64,
+}
+
+impl<S, F> Server<S, F>
+where
+    S: State,
+    F: Fn(&ConnectFour<S>, Option<usize>) -> Result<Box<dyn PlayerTrait<Game=ConnectFour<S>>>, String> + Send + Sync,
+{
+    /// `game` is the default board, `new_player` creates the player for every search, with
+    /// the number of simulations if the request limits them. Positions on boards that `S` or
+    /// the player do not support are rejected with `422`.
+    pub fn new(game: ConnectFour<S>, new_player: F) -> Server<S, F> {
+        Server { game, new_player, games: Mutex::new(HashMap::new()), next_id: AtomicU64::new(1) }
+    }
+
+    /// Answers a request for `path` with the given method and body.
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> Response {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<_> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        let body = || {
+            if body.trim().is_empty() {
+                Ok(Json::Object(vec![]))
+            }
+            else {
+                Json::parse(body).map_err(|message| error(400, format!("invalid JSON: {}", message)))
+            }
+        };
+        let result = match (method, &segments[..]) {
+            ("POST", ["analyse"]) => body().and_then(|body| self.search(&body, true)),
+            ("POST", ["bestmove"]) => body().and_then(|body| self.search(&body, false)),
+            ("POST", ["games"]) => body().and_then(|body| self.create(&body)),
+            ("GET", ["games", id]) => self.with_game(id, |id, entry| Ok((200, state(id, entry)))),
+            ("DELETE", ["games", id]) => self.delete(id),
+            ("POST", ["games", id, "moves"]) => body().and_then(|body| self.play(id, &body)),
+            (_, ["analyse" | "bestmove" | "games"] | ["games", _] | ["games", _, "moves"]) => {
+                Err(error(405, format!("{} is not allowed on {}", method, path)))
+            }
+            _ => Err(error(404, format!("nothing at {}", path))),
+        };
+        result.unwrap_or_else(|response| response)
+    }
+
+    fn position(&self, body: &Json) -> Result<ConnectFour<S>, Response> {
+        if let Some(notation) = body.get("position") {
+            let notation = notation.as_str().ok_or_else(|| error(400, "`position` must be a string"))?;
+            return ConnectFour::from_notation(notation).map_err(|message| error(422, message));
+        }
+
+        let (columns, rows) = self.game.size();
+        let columns = number(body, "columns")?.unwrap_or(columns as u64) as usize;
+        let rows = number(body, "rows")?.unwrap_or(rows as u64) as usize;
+        let win_length = number(body, "win_length")?.unwrap_or(self.game.win_length() as u64) as usize;
+        let mut game = ConnectFour::with_win_length(columns, rows, win_length).map_err(|()| {
+            error(422, format!("unsupported board {}x{} with win length {}", columns, rows, win_length))
+        })?;
+        let moves = match body.get("moves") {
+            Some(moves) => moves.as_array().ok_or_else(|| error(400, "`moves` must be an array"))?,
+            None => &[],
+        };
+        for move_ in moves {
+            let move_ = move_.as_u64().ok_or_else(|| error(400, format!("invalid move {}", move_)))? as usize;
+            play(&mut game, move_)?;
+        }
+        Ok(game)
+    }
+
+    fn search(&self, body: &Json, analyse: bool) -> Result<Response, Response> {
+        let game = self.position(body)?;
+        if game.has_ended() {
+            return Err(error(422, "the game has ended"));
+        }
+        let simulations = number(body, "simulations")?
+            .map(|simulations| simulations.min(MAX_SIMULATIONS as u64) as usize);
+        let movetime = number(body, "movetime")?.map_or(MAX_MOVETIME, |movetime| Duration::from_millis(movetime).min(MAX_MOVETIME));
+        let deadline = Instant::now() + movetime;
+        let stop = || Instant::now() >= deadline;
+
+        let player = (self.new_player)(&game, simulations).map_err(|message| error(422, message))?;
+        let ((move_, statistics), info) = player.think(&game, &stop);
+        let mut members = vec![
+            ("bestmove", Json::from(move_)),
+            ("depth", Json::from(info.depth)),
+            ("visits", Json::from(info.visits)),
+            ("value", Json::from(info.value)),
+            ("pv", Json::from(info.principal_variation)),
+        ];
+        if analyse {
+            let moves = statistics.iter()
+                .map(|statistics| Json::object(vec![
+                    ("move", Json::from(statistics.move_)),
+                    ("visits", Json::from(statistics.visits)),
+                    ("value", Json::from(statistics.value)),
+                ]))
+                .collect();
+            members.push(("moves", Json::Array(moves)));
+            if let Some(report) = info.report {
+                members.push(("report", report_json(&report)));
+            }
+        }
+        Ok((200, Json::object(members)))
+    }
+
+    fn create(&self, body: &Json) -> Result<Response, Response> {
+        let entry = Entry { game: self.position(body)?, moves: vec![] };
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let response = (201, state(id, &entry));
+        self.games.lock().unwrap().insert(id, entry);
+        Ok(response)
+    }
+
+    fn play(&self, id: &str, body: &Json) -> Result<Response, Response> {
+        let move_ = body.get("move")
+            .and_then(Json::as_u64)
+            .ok_or_else(|| error(400, "expected a column in `move`"))? as usize;
+        self.with_game(id, |id, entry| {
+            play(&mut entry.game, move_)?;
+            entry.moves.push(move_);
+            Ok((200, state(id, entry)))
+        })
+    }
+
+    fn delete(&self, id: &str) -> Result<Response, Response> {
+        let id = parse_id(id)?;
+        match self.games.lock().unwrap().remove(&id) {
+            Some(_) => Ok((200, Json::object(vec![("id", Json::from(id))]))),
+            None => Err(error(404, format!("no game {}", id))),
+        }
+    }
+
+    fn with_game(&self, id: &str, f: impl FnOnce(u64, &mut Entry<S>) -> Result<Response, Response>) -> Result<Response, Response> {
+        let id = parse_id(id)?;
+        match self.games.lock().unwrap().get_mut(&id) {
+            Some(entry) => f(id, entry),
+            None => Err(error(404, format!("no game {}", id))),
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Result<u64, Response> {
+    id.parse().map_err(|_| error(404, format!("no game {}", id)))
+}
+
+/// The optional whole number `name` of `body`.
+fn number(body: &Json, name: &str) -> Result<Option<u64>, Response> {
+    match body.get(name) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| error(400, format!("`{}` must be a whole number", name))),
+    }
+}
+
+fn play<S: State>(game: &mut ConnectFour<S>, move_: usize) -> Result<(), Response> {
+    if game.has_ended() {
+        return Err(error(422, "the game has ended"));
+    }
+    game.play(move_).map(|_| ()).map_err(|invalid_move| error(422, format!("invalid move {}: {:?}", move_, invalid_move)))
+}
+
+fn state<S: State>(id: u64, entry: &Entry<S>) -> Json {
+    let game = &entry.game;
+    let player = |Player(player)| Json::from(player as usize);
+    let (to_move, result) = match game.winner() {
+        Some(_) => (Json::Null, "won"),
+        None if game.has_ended() => (Json::Null, "draw"),
+        None => (player(game.current_player()), "ongoing"),
+    };
+    Json::object(vec![
+        ("id", Json::from(id)),
+        ("position", Json::from(game.notation())),
+        ("moves", Json::from(entry.moves.clone())),
+        ("to_move", to_move),
+        ("winner", game.winner().map_or(Json::Null, player)),
+        ("result", Json::from(result)),
+        ("valid_moves", Json::from(if game.has_ended() { vec![] } else { game.valid_moves() })),
+    ])
+}
+
+fn report_json(report: &Report<usize>) -> Json {
+    let rate = |rate: Rate| Json::object(vec![
+        ("estimate", Json::from(rate.estimate)),
+        ("lower", Json::from(rate.lower)),
+        ("upper", Json::from(rate.upper)),
+    ]);
+    let moves = report.moves.iter()
+        .map(|report| Json::object(vec![
+            ("move", Json::from(report.move_)),
+            ("playouts", Json::from(report.outcomes.playouts())),
+            ("wins", rate(report.wins)),
+            ("draws", rate(report.draws)),
+            ("losses", rate(report.losses)),
+            ("value", rate(report.value)),
+        ]))
+        .collect();
+    Json::object(vec![
+        ("separated", Json::from(report.is_separated())),
+        ("moves", Json::Array(moves)),
+    ])
+}
+
+
+/// Accepts connections on `listener` forever, one request per connection. Requests are read on
+/// a thread of their own, since slow clients may take up to `READ_TIMEOUT`, and only handled
+/// on the rayon pool.
+pub fn serve<S, F>(server: Arc<Server<S, F>>, listener: TcpListener) -> io::Result<()>
+where
+    S: State + 'static,
+    F: Fn(&ConnectFour<S>, Option<usize>) -> Result<Box<dyn PlayerTrait<Game=ConnectFour<S>>>, String> + Send + Sync + 'static,
+{
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let server = server.clone();
+        thread::spawn(move || {
+            let request = stream.set_read_timeout(Some(READ_TIMEOUT)).and_then(|()| read_request(&stream));
+            match request {
+                Ok(Ok((method, path, body))) => rayon::spawn(move || {
+                    // A panicking player must not take the whole pool down.
+                    let response = panic::catch_unwind(AssertUnwindSafe(|| server.handle(&method, &path, &body)))
+                        .unwrap_or_else(|_| error(500, "the search failed"));
+                    respond(&stream, response);
+                }),
+                Ok(Err(response)) => respond(&stream, response),
+                Err(error) => log::debug!("could not read a request: {}", error),
+            }
+        });
+    }
+    Ok(())
+}
+
+fn respond(stream: &TcpStream, response: Response) {
+    if let Err(error) = write_response(stream, response) {
+        log::debug!("could not answer a request: {}", error);
+    }
+}
+
+fn write_response(stream: &TcpStream, (status, body): Response) -> io::Result<()> {
+    let body = body.to_string();
+    let mut output = io::BufWriter::new(stream);
+    write!(
+        output,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason(status), body.len(), body,
+    )?;
+    output.flush()
+}
+
+/// The method, path and body of the request on `stream`, or the response to a malformed one.
+fn read_request(stream: &TcpStream) -> io::Result<Result<(String, String, String), Response>> {
+    let mut input = io::BufReader::new(stream.take(MAX_REQUEST_SIZE));
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    let mut words = line.split_whitespace();
+    let (Some(method), Some(path)) = (words.next(), words.next()) else {
+        return Ok(Err(error(400, "malformed request line")));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(Err(error(400, "incomplete headers")));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(length) => content_length = length,
+                    Err(_) => return Ok(Err(error(400, "invalid Content-Length"))),
+                }
+            }
+        }
+    }
+
+    if content_length > MAX_REQUEST_SIZE {
+        return Ok(Err(error(413, "the request is too large")));
+    }
+    let mut body = vec![];
+    input.take(content_length).read_to_end(&mut body)?;
+    if (body.len() as u64) < content_length {
+        return Ok(Err(error(400, "incomplete body")));
+    }
+    match String::from_utf8(body) {
+        Ok(body) => Ok(Ok((method, path, body))),
+        Err(_) => Ok(Err(error(400, "the body is not UTF-8"))),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Allocation, BitState, MonteCarloPlayer, SearchConfig, TreeSearchPlayer, VecState};
+
+    type Connect4 = ConnectFour<BitState>;
+
+    type NewPlayer = fn(&Connect4, Option<usize>) -> Result<Box<dyn PlayerTrait<Game=Connect4>>, String>;
+
+    /// Like a network player, the server's player only plays on 7×6 boards.
+    fn server() -> Server<BitState, NewPlayer> {
+        Server::new(Connect4::new(7, 6).unwrap(), |game, simulations| {
+            if game.size() != (7, 6) {
+                return Err("the player only plays on 7x6 boards".to_string());
+            }
+            let config = SearchConfig { simulations: simulations.unwrap_or(1000), ..SearchConfig::default() };
+            Ok(Box::new(TreeSearchPlayer::with_config(config)))
+        })
+    }
+
+    #[test]
+    fn searches_answer_statistics_and_best_moves() {
+        let server = server();
+        let (status, analysis) = server.handle("POST", "/analyse", r#"{"moves": [3, 3], "simulations": 500}"#);
+        assert_eq!(status, 200);
+        let moves = analysis.get("moves").and_then(Json::as_array).unwrap();
+        assert_eq!(moves.len(), 7);
+        // The best move is a most visited one, but not necessarily the first of several.
+        let best = moves.iter().find(|move_| move_.get("move") == analysis.get("bestmove")).unwrap();
+        assert_eq!(best.get("visits"), moves[0].get("visits"));
+        assert_eq!(analysis.get("report"), None);
+
+        let position = r#"{"position": "4 ......./......./......./......./...ooO./...xxx.", "movetime": 50}"#;
+        let (status, best_move) = server.handle("POST", "/bestmove", position);
+        assert_eq!(status, 200);
+        assert!(matches!(best_move.get("bestmove"), Some(Json::Number(move_)) if *move_ == 2. || *move_ == 6.));
+        assert_eq!(best_move.get("moves"), None);
+
+        assert_eq!(server.handle("POST", "/bestmove", r#"{"moves": [9]}"#).0, 422);
+        let (status, rejected) = server.handle("POST", "/bestmove", r#"{"columns": 6, "rows": 5}"#);
+        assert_eq!((status, rejected.get("error").and_then(Json::as_str)), (422, Some("the player only plays on 7x6 boards")));
+        assert_eq!(server.handle("POST", "/bestmove", "{").0, 400);
+        assert_eq!(server.handle("GET", "/analyse", "").0, 405);
+    }
+
+    #[test]
+    fn monte_carlo_analyses_include_the_report() {
+        let server = Server::new(Connect4::new(7, 6).unwrap(), |_, _| -> Result<Box<dyn PlayerTrait<Game=Connect4>>, String> {
+            Ok(Box::new(MonteCarloPlayer::with_budget(50, Allocation::Uniform)))
+        });
+        let (status, analysis) = server.handle("POST", "/analyse", r#"{"moves": [3]}"#);
+        assert_eq!(status, 200);
+        let report = analysis.get("report").unwrap();
+        assert!(matches!(report.get("separated"), Some(Json::Bool(_))));
+        let moves = report.get("moves").and_then(Json::as_array).unwrap();
+        assert_eq!(moves.len(), 7);
+        assert_eq!(moves[0].get("move"), analysis.get("bestmove"));
+        assert_eq!(moves[0].get("playouts").and_then(Json::as_u64), Some(50));
+        let value = moves[0].get("value").unwrap();
+        let bound = |name| value.get(name).and_then(Json::as_f64).unwrap();
+        assert!(bound("lower") <= bound("estimate") && bound("estimate") <= bound("upper"));
+    }
+
+    #[test]
+    fn malicious_requests_are_rejected() {
+        let server = Server::new(ConnectFour::<VecState>::new(7, 6).unwrap(), |_, simulations| -> Result<Box<dyn PlayerTrait<Game=ConnectFour<VecState>>>, String> {
+            assert!(simulations.unwrap_or(0) <= MAX_SIMULATIONS);
+            Ok(Box::new(TreeSearchPlayer::with_config(SearchConfig { simulations: 10, ..SearchConfig::default() })))
+        });
+        assert_eq!(server.handle("POST", "/bestmove", r#"{"simulations": 1e15, "movetime": 1e15}"#).0, 200);
+        let deep = "[".repeat(100_000);
+        assert_eq!(server.handle("POST", "/games", &deep).0, 400);
+        let huge = format!(r#"{{"columns": {}, "rows": {}}}"#, u64::MAX, u64::MAX);
+        assert_eq!(server.handle("POST", "/games", &huge).0, 422);
+        assert_eq!(server.handle("POST", "/games", r#"{"columns": 4294967296, "rows": 4294967296}"#).0, 422);
+        assert_eq!(server.handle("POST", "/games", r#"{"columns": 65, "rows": 6}"#).0, 422);
+    }
+
+    #[test]
+    fn games_keep_their_state() {
+        let server = server();
+        let (status, created) = server.handle("POST", "/games", "");
+        assert_eq!(status, 201);
+        let id = created.get("id").and_then(Json::as_u64).unwrap();
+        for &move_ in &[0, 1, 0, 1, 0, 1] {
+            let (status, _) = server.handle("POST", &format!("/games/{}/moves", id), &format!(r#"{{"move": {}}}"#, move_));
+            assert_eq!(status, 200);
+        }
+        let (status, state) = server.handle("POST", &format!("/games/{}/moves", id), r#"{"move": 0}"#);
+        assert_eq!(status, 200);
+        assert_eq!(state.get("result").and_then(Json::as_str), Some("won"));
+        assert_eq!(state.get("winner"), Some(&Json::Number(1.)));
+        assert_eq!(state.get("moves").and_then(Json::as_array).map(<[Json]>::len), Some(7));
+        assert_eq!(server.handle("POST", &format!("/games/{}/moves", id), r#"{"move": 2}"#).0, 422);
+
+        assert_eq!(server.handle("GET", &format!("/games/{}", id), ""), (200, state));
+        assert_eq!(server.handle("DELETE", &format!("/games/{}", id), "").0, 200);
+        assert_eq!(server.handle("GET", &format!("/games/{}", id), "").0, 404);
+    }
+
+    #[test]
+    fn requests_are_answered_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve(Arc::new(server()), listener));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        let body = r#"{"columns": 5, "rows": 4}"#;
+        write!(stream, "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 201 Created\r\n"), "{}", response);
+        let state = Json::parse(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(state.get("position").and_then(Json::as_str), Some("4 ...../...../...../....."));
+    }
+}
@@ -1,14 +1,74 @@
+use std::fmt;
 use std::io;
 use std::io::prelude::*;
 use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
 
 pub mod ai_player;
 pub mod connect_four;
+pub mod dataset;
+pub mod elo;
+pub mod engine;
+pub mod engine_spec;
+pub mod events;
 mod game;
+pub mod http;
+pub mod json;
+pub mod persist;
+pub mod remote;
+pub mod tablebase;
+pub mod tactics;
+pub mod tui;
 
-pub use crate::ai_player::{MonteCarloPlayer, TreeSearchPlayer};
-pub use crate::game::{Game, Player, Winner};
+pub use crate::ai_player::{
+    Allocation, Difficulty, DifficultyPlayer, GraphSearchPlayer, MinimaxPlayer, MonteCarloPlayer, MoveStatistics,
+    Network, NetworkPlayer, Report, SearchConfig, SearchGraph, SearchTree, TreeSearchPlayer,
+};
+pub use crate::engine_spec::EngineSpec;
+pub use crate::game::{Action, Evaluate, Event, Game, Moves, Notation, Oracle, Player, PositionKey, Winner};
 pub use crate::connect_four::*;
+pub use crate::tablebase::{Tablebase, TablebasePlayer};
+
+
+/// The move chosen by a player together with the statistics of all moves it searched, most
+/// visited first.
+pub type Analysis<M> = (M, Vec<MoveStatistics<M>>);
+
+/// The result of `PlayerTrait::think`.
+pub type Thought<M> = (Analysis<M>, SearchInfo<M>);
+
+
+/// A summary of a search, as reported by engines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchInfo<M> {
+    /// The number of plies the search looked ahead along the principal variation.
+    pub depth: usize,
+    /// Playouts for Monte Carlo searches, searched positions for alpha-beta searches.
+    pub visits: u64,
+    /// The expected result of the chosen move for the player to move, valued like
+    /// `MoveStatistics::value`, if the search estimates one.
+    pub value: Option<f64>,
+    /// The chosen move followed by the best replies as far as the search looked.
+    pub principal_variation: Vec<M>,
+    /// The playout statistics of every move with confidence intervals, for players that
+    /// estimate them.
+    pub report: Option<Report<M>>,
+}
+
+impl<M: Copy + PartialEq> SearchInfo<M> {
+    /// The summary of a search that only reports the statistics of the moves at the root.
+    pub fn from_statistics(move_: M, statistics: &[MoveStatistics<M>]) -> SearchInfo<M> {
+        SearchInfo {
+            depth: 1,
+            visits: statistics.iter().map(|statistics| statistics.visits).sum(),
+            value: statistics.iter()
+                .find(|statistics| statistics.move_ == move_)
+                .map(|statistics| statistics.value),
+            principal_variation: vec![move_],
+            report: None,
+        }
+    }
+}
 
 
 pub trait PlayerTrait {
@@ -16,70 +76,238 @@ pub trait PlayerTrait {
 
     fn make_move(&self, game: &Self::Game) -> <Self::Game as Game>::Move;
 
+    /// Chooses a move like `make_move` and also returns the search statistics behind the
+    /// choice, most visited move first. Players that do not search return no statistics.
+    fn analyse(&self, game: &Self::Game) -> Analysis<<Self::Game as Game>::Move> {
+        (self.make_move(game), vec![])
+    }
+
+    /// Chooses a move like `analyse`, but stops searching early once `stop` returns `true`, and
+    /// also summarises the search. Players that cannot be interrupted search as usual.
+    fn think(&self, game: &Self::Game, _stop: &dyn Fn() -> bool) -> Thought<<Self::Game as Game>::Move> {
+        let (move_, statistics) = self.analyse(game);
+        let info = SearchInfo::from_statistics(move_, &statistics);
+        ((move_, statistics), info)
+    }
+
+    /// Chooses what to do on this turn. Only players with a user interface do anything but
+    /// move.
+    fn act(&self, game: &Self::Game) -> Action<<Self::Game as Game>::Move> {
+        Action::Move(self.make_move(game))
+    }
+
+    /// Whether the player agrees to a draw offered by the player to move in `game`. Players
+    /// accept if their analysis expects at least a draw for the opponent.
+    fn accepts_draw(&self, game: &Self::Game) -> bool {
+        let (_, statistics) = self.analyse(game);
+        statistics.first().is_some_and(|best| best.value >= 0.5)
+    }
+
     fn invalid_move(&self, _move: <Self::Game as Game>::InvalidMove) {
         // ignore by default
     }
 }
 
 
-#[derive(Clone, Copy)]
+/// The move of a player that can't be asked any more, e. g. because its input has ended, but
+/// has to move in `PlayerTrait::make_move`: the first valid move. `act` quits instead.
+pub(crate) fn fallback_move<G: Game>(game: &G) -> G::Move {
+    game.valid_moves()[0]
+}
+
+
+/// A player that is asked for its actions on a text stream, by default on the terminal.
+///
+/// Clones share the streams, which is also why the player is not `Copy`.
+#[derive(Clone)]
 pub struct HumanPlayer<G: Game> {
+    input: Arc<Mutex<Box<dyn BufRead + Send>>>,
+    output: Arc<Mutex<Box<dyn Write + Send>>>,
     _game: PhantomData<G>,
 }
 
 impl<G: Game> HumanPlayer<G> {
+    /// A player on standard input and output.
     pub fn new() -> HumanPlayer<G> {
+        // Reading byte by byte leaves the rest of the input to other players on the same
+        // terminal, e. g. in hot seat games.
+        HumanPlayer::with_io(io::BufReader::with_capacity(1, io::stdin()), io::stdout())
+    }
+
+    /// A player that reads its input from `input` and writes prompts and messages to `output`.
+    /// The player quits once the input ends.
+    pub fn with_io(input: impl BufRead + Send + 'static, output: impl Write + Send + 'static) -> HumanPlayer<G> {
         HumanPlayer {
+            input: Arc::new(Mutex::new(Box::new(input))),
+            output: Arc::new(Mutex::new(Box::new(output))),
             _game: Default::default(),
         }
     }
+
+    fn write(&self, message: fmt::Arguments) {
+        let mut output = self.output.lock().unwrap();
+        // Without an output the player can still play, so errors are ignored.
+        let _ = output.write_fmt(message).and_then(|_| output.flush());
+    }
+
+    /// The next line of input without the line break, or `None` once the input has ended or
+    /// failed.
+    fn read_line(&self) -> Option<String> {
+        let mut line = String::new();
+        match self.input.lock().unwrap().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()),
+        }
+    }
+}
+
+impl<G: Game> Default for HumanPlayer<G> {
+    fn default() -> HumanPlayer<G> {
+        Self::new()
+    }
 }
 
-impl<G: Game> PlayerTrait for HumanPlayer<G> {
+const HUMAN_PLAYER_HELP: &str = "\
+Enter a column to place a stone there, or one of these commands:
+  undo       take back your previous move
+  hint       ask the AI for a suggestion
+  save FILE  save the moves played so far
+  resign     give up
+  draw?      offer a draw
+  show       show the board again
+  quit       end the game without a result
+  help       show this help";
+
+/// The number of playouts behind a hint.
+pub const HINT_SIMULATIONS: usize = 10_000;
+
+impl<G: Game> PlayerTrait for HumanPlayer<G> where G::State: fmt::Display {
     type Game = G;
 
+    /// Plays `fallback_move` if the input ends before a move is entered. Games are driven with
+    /// `act`, which quits instead.
     fn make_move(&self, game: &G) -> G::Move {
-        let stdin = io::stdin();
-        let mut lines = stdin.lock().lines();
+        loop {
+            match self.act(game) {
+                Action::Move(move_) => return move_,
+                Action::Quit => {
+                    let move_ = fallback_move(game);
+                    log::warn!("the input has ended, playing {}", move_);
+                    return move_;
+                }
+                _ => self.write(format_args!("Only moves are possible here.\n")),
+            }
+        }
+    }
 
+    fn act(&self, game: &G) -> Action<G::Move> {
         loop {
-            print!("Player {}, place a stone. Which column? ", game.current_player());
-            io::stdout().flush().unwrap();
-
-            let input = lines.next().expect("Input failed").expect("Input failed");
-            match input.parse::<G::Move>() {
-                Ok(position) => return position,
-                Err(_) => {
-                    println!("Please enter a number.");
+            self.write(format_args!("Player {}, place a stone. Which column? ", game.current_player()));
+
+            let Some(input) = self.read_line() else {
+                return Action::Quit;
+            };
+            let input = input.trim();
+            if let Ok(move_) = input.parse::<G::Move>() {
+                return Action::Move(move_);
+            }
+            let (command, argument) = input.split_once(' ').unwrap_or((input, ""));
+            match (command, argument.trim()) {
+                ("undo", "") => return Action::Undo,
+                ("hint", "") => {
+                    let config = SearchConfig { simulations: HINT_SIMULATIONS, ..SearchConfig::default() };
+                    let hint = TreeSearchPlayer::with_config(config).make_move(game);
+                    self.write(format_args!("The AI suggests column {}.\n", hint));
                 }
+                ("save", path) if !path.is_empty() => return Action::Save(path.into()),
+                ("resign", "") => return Action::Resign,
+                ("draw?", "") => return Action::OfferDraw,
+                ("show", "") => self.write(format_args!("{}", game.state())),
+                ("quit", "") => return Action::Quit,
+                ("help", "") => self.write(format_args!("{}\n", HUMAN_PLAYER_HELP)),
+                _ => self.write(format_args!("Unknown command `{}`, enter `help` for a list of commands.\n", input)),
+            }
+        }
+    }
+
+    /// Declines once the input has ended.
+    fn accepts_draw(&self, game: &G) -> bool {
+        loop {
+            self.write(format_args!("Player {} offers a draw. Do you accept? [y/n] ", game.current_player()));
+
+            let Some(input) = self.read_line() else {
+                return false;
+            };
+            match input.trim() {
+                "y" | "yes" => return true,
+                "n" | "no" => return false,
+                _ => {}
             }
         }
     }
 
     fn invalid_move(&self, move_: <Self::Game as Game>::InvalidMove) {
-        println!("Invalid input: {:?}", move_)
+        self.write(format_args!("Invalid input: {:?}\n", move_))
     }
 }
 
 
-#[derive(Copy, Clone)]
+/// A `HumanPlayer` that is told the move the AI would choose before every turn, along with the
+/// playout statistics of every move behind the choice.
+#[derive(Clone)]
 pub struct AiAidedPlayer<G: Game> {
-    _game: PhantomData<G>,
+    human: HumanPlayer<G>,
 }
 
 impl<G: Game> AiAidedPlayer<G> {
     pub fn new() -> AiAidedPlayer<G> {
         AiAidedPlayer {
-            _game: Default::default(),
+            human: HumanPlayer::new(),
+        }
+    }
+
+    /// See `HumanPlayer::with_io`.
+    pub fn with_io(input: impl BufRead + Send + 'static, output: impl Write + Send + 'static) -> AiAidedPlayer<G> {
+        AiAidedPlayer {
+            human: HumanPlayer::with_io(input, output),
+        }
+    }
+}
+
+impl<G: Game> Default for AiAidedPlayer<G> {
+    fn default() -> AiAidedPlayer<G> {
+        Self::new()
+    }
+}
+
+impl<G: Game + 'static> AiAidedPlayer<G> {
+    fn suggest(&self, game: &G) {
+        let ((move_, _), info) = MonteCarloPlayer::default().think(game, &|| false);
+        if let Some(report) = info.report {
+            self.human.write(format_args!("{}", report));
         }
+        self.human.write(format_args!("The AI would choose column {}.\n", move_));
     }
 }
 
-impl<G: Game + 'static> PlayerTrait for AiAidedPlayer<G> {
+impl<G: Game + 'static> PlayerTrait for AiAidedPlayer<G> where G::State: fmt::Display {
     type Game = G;
 
     fn make_move(&self, game: &G) -> G::Move {
-        println!("The AI would choose column {}.", MonteCarloPlayer::default().make_move(game));
-        HumanPlayer::new().make_move(game)
+        self.suggest(game);
+        self.human.make_move(game)
+    }
+
+    fn act(&self, game: &G) -> Action<G::Move> {
+        self.suggest(game);
+        self.human.act(game)
+    }
+
+    fn accepts_draw(&self, game: &G) -> bool {
+        self.human.accepts_draw(game)
+    }
+
+    fn invalid_move(&self, move_: G::InvalidMove) {
+        self.human.invalid_move(move_)
     }
 }
@@ -1,25 +1,667 @@
-use rand::{thread_rng, seq::SliceRandom};
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, IsTerminal, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::{rngs::StdRng, FromEntropy, Rng, SeedableRng};
 
 use mcc4::*;
+use mcc4::ai_player::{self, SIMULATIONS};
+use mcc4::engine_spec::{Board, Selection};
+use mcc4::connect_four::render::{self, Ansi, Palette, Renderer};
+use mcc4::events::EventStream;
+use mcc4::http;
+use mcc4::remote::{self, Notice, RemotePlayer};
+use mcc4::tui::Tui;
+
+const USAGE: &str = "\
+usage: mcc4 [OPTIONS]
+       mcc4 engine [OPTIONS]
+       mcc4 serve [OPTIONS]
+       mcc4 connect HOST[:PORT] [OPTIONS]
+       mcc4 http [OPTIONS]
+
+The second form speaks a text protocol on standard input and output, so that other programs
+can use the player of the second seat. See the documentation of `mcc4::engine` for the
+commands.
+
+`serve` waits for an opponent who joins with `connect` from another machine. The first seat
+plays on each side, the host chooses the board and who moves first.
+
+`http` answers analysis and play requests with JSON on localhost, using the player of the
+second seat. See the documentation of `mcc4::http` for the endpoints.
+
+options:
+  --variant standard|five-in-a-row|small  board size and win length presets (default: standard)
+  --columns N, --rows N                   board size (default: 7 columns, 6 rows)
+  --win-length N                          stones in a line needed to win (default: 4)
+  --player1 SEAT, --player2 SEAT          who plays in each seat (default: human and mcts)
+  --first 1|2|random                      the seat that moves first (default: random)
+  --difficulty LEVEL                      shorthand for `--player2 difficulty:LEVEL`
+  --hot-seat                              two humans take turns at this terminal
+  --spectate                              watch tree search play against Monte Carlo
+  --simulations N                         playouts per move of Monte Carlo, tree search and
+                                          network seats
+  --time SECONDS                          thinking time per move of solver seats
+  --seed N                                seed for all random decisions
+  --load FILE                             continue a game saved with the `save` command
+  --port N                                the port to serve on (default: 4004, 4005 for http)
+  --style STYLE                           how the board is drawn (default: auto)
+  --interface auto|tui|text               full screen or line based (default: full screen for
+                                          humans on terminals)
+  --events FILE|tcp:HOST:PORT             write the events of the game as JSON lines, see
+                                          `mcc4::events`
+  -h, --help                              print this help
+
+seats:
+  human                         moves are read from the terminal
+  aided                         a human who is shown the move of a Monte Carlo player
+  montecarlo[:SIMULATIONS]      flat Monte Carlo search
+  mcts[:POLICY][:SIMULATIONS]   Monte Carlo tree search, selecting with ucb1 (default),
+                                ucb1-tuned, thompson or puct
+  graph[:SIMULATIONS]           Monte Carlo tree search sharing transpositions
+  solver[:SECONDS]              alpha-beta search
+  network:FILE                  tree search guided by a network trained for the board
+  [difficulty:]LEVEL            beginner, easy, medium, hard, expert or perfect
+`monte-carlo` and `tree-search` are other names for `montecarlo` and `mcts`.
+
+styles:
+  auto          ansi on terminals, ascii otherwise
+  ansi          coloured stones
+  colour-blind  coloured stones in colours that are easier to tell apart
+  ascii         X and O
+  unicode       black and white discs
+  compact       the board on a single line";
+
+
+#[derive(Clone, Debug, PartialEq)]
+enum Seat {
+    Human,
+    Aided,
+    Engine(EngineSpec),
+}
+
+impl FromStr for Seat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Seat, String> {
+        match s {
+            "human" => Ok(Seat::Human),
+            "aided" => Ok(Seat::Aided),
+            _ => s.parse().map(Seat::Engine),
+        }
+    }
+}
+
+impl Seat {
+    fn is_human(&self) -> bool {
+        matches!(self, Seat::Human | Seat::Aided)
+    }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Style {
+    Ansi,
+    ColourBlind,
+    Ascii,
+    Unicode,
+    Compact,
+}
+
+fn is_capable_terminal() -> bool {
+    let dumb = env::var("TERM").is_ok_and(|term| term == "dumb");
+    io::stdout().is_terminal() && !dumb
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Style, String> {
+        match s {
+            "ansi" => Ok(Style::Ansi),
+            "colour-blind" | "color-blind" => Ok(Style::ColourBlind),
+            "ascii" => Ok(Style::Ascii),
+            "unicode" => Ok(Style::Unicode),
+            "compact" => Ok(Style::Compact),
+            _ => Err(format!("unknown style `{}`", s)),
+        }
+    }
+}
+
+impl Style {
+    /// Colours only where escape sequences are understood, so that redirected output stays
+    /// readable.
+    fn detect() -> Style {
+        if is_capable_terminal() { Style::Ansi } else { Style::Ascii }
+    }
+
+    fn is_ansi(self) -> bool {
+        matches!(self, Style::Ansi | Style::ColourBlind)
+    }
+
+    fn renderer(self) -> Box<dyn Renderer> {
+        match self {
+            Style::Ansi => Box::new(Ansi { palette: Palette::Standard }),
+            Style::ColourBlind => Box::new(Ansi { palette: Palette::ColourBlind }),
+            Style::Ascii => Box::new(render::Ascii),
+            Style::Unicode => Box::new(render::Unicode),
+            Style::Compact => Box::new(render::Compact),
+        }
+    }
+}
+
+
+#[derive(Clone)]
+struct Options {
+    columns: usize,
+    rows: usize,
+    win_length: usize,
+    seats: [Seat; 2],
+    /// The index of the seat that moves first, random if `None`.
+    first: Option<usize>,
+    simulations: Option<usize>,
+    time: Option<Duration>,
+    seed: Option<u64>,
+    /// A game saved by a human player to continue.
+    load: Option<String>,
+    /// Detected from the output if `None`.
+    style: Option<Style>,
+    /// Whether to use the full screen interface, detected from the terminal if `None`.
+    tui: Option<bool>,
+    /// Where to write the events of the game.
+    events: Option<String>,
+    /// The default port of the mode if `None`.
+    port: Option<u16>,
+    mode: Mode,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Mode {
+    Play,
+    Engine,
+    Serve,
+    Http,
+    /// Join a game served at the address.
+    Connect(String),
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            columns: 7,
+            rows: 6,
+            win_length: 4,
+            seats: [Seat::Human, Seat::Engine(EngineSpec::TreeSearch { selection: Selection::Ucb1, simulations: None })],
+            first: None,
+            simulations: None,
+            time: None,
+            seed: None,
+            load: None,
+            style: None,
+            tui: None,
+            events: None,
+            port: None,
+            mode: Mode::Play,
+        }
+    }
+}
+
+
+fn parse_number<T: FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid {} `{}`", name, value))
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = parse_number(value, "number of seconds")?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid number of seconds `{}`", value))
+}
+
+fn parse_arguments() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut arguments = env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        let mut value = || arguments.next().ok_or(format!("missing value for `{}`", argument));
+        match argument.as_str() {
+            "--variant" => {
+                let (columns, rows, win_length) = match value()?.as_str() {
+                    "standard" => (7, 6, 4),
+                    "five-in-a-row" => (9, 6, 5),
+                    "small" => (5, 4, 4),
+                    variant => return Err(format!("unknown variant `{}`", variant)),
+                };
+                options = Options { columns, rows, win_length, ..options };
+            }
+            "--columns" => options.columns = parse_number(&value()?, "number of columns")?,
+            "--rows" => options.rows = parse_number(&value()?, "number of rows")?,
+            "--win-length" => options.win_length = parse_number(&value()?, "win length")?,
+            "--player1" => options.seats[0] = value()?.parse()?,
+            "--player2" => options.seats[1] = value()?.parse()?,
+            "--first" => {
+                options.first = match value()?.as_str() {
+                    "1" => Some(0),
+                    "2" => Some(1),
+                    "random" => None,
+                    first => return Err(format!("invalid seat `{}`, expected 1, 2 or random", first)),
+                };
+            }
+            "--difficulty" | "-d" => options.seats[1] = Seat::Engine(EngineSpec::Difficulty(value()?.parse()?)),
+            "--hot-seat" => options.seats = [Seat::Human, Seat::Human],
+            "--spectate" => {
+                options.seats = [
+                    Seat::Engine(EngineSpec::TreeSearch { selection: Selection::Ucb1, simulations: None }),
+                    Seat::Engine(EngineSpec::MonteCarlo { simulations: None }),
+                ];
+            }
+            "--simulations" => options.simulations = Some(parse_number(&value()?, "simulations")?),
+            "--time" => options.time = Some(parse_seconds(&value()?)?),
+            "--seed" => options.seed = Some(parse_number(&value()?, "seed")?),
+            "--load" => options.load = Some(value()?),
+            "--style" => {
+                options.style = match value()?.as_str() {
+                    "auto" => None,
+                    style => Some(style.parse()?),
+                };
+            }
+            "--interface" => {
+                options.tui = match value()?.as_str() {
+                    "auto" => None,
+                    "tui" => Some(true),
+                    "text" => Some(false),
+                    interface => return Err(format!("unknown interface `{}`", interface)),
+                };
+            }
+            "--events" => options.events = Some(value()?),
+            "--port" => options.port = Some(parse_number(&value()?, "port")?),
+            "engine" if options.mode == Mode::Play => options.mode = Mode::Engine,
+            "serve" if options.mode == Mode::Play => options.mode = Mode::Serve,
+            "http" if options.mode == Mode::Play => options.mode = Mode::Http,
+            "connect" if options.mode == Mode::Play => options.mode = Mode::Connect(value()?),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unexpected argument `{}`", argument)),
+        }
+    }
+    Ok(options)
+}
+
+
+/// The player of `seat`, or why it cannot play on the board of `game`, e. g. a network
+/// trained for another size.
+fn player<S: Board>(seat: &Seat, game: &ConnectFour<S>, options: &Options) -> Result<Box<dyn PlayerTrait<Game=ConnectFour<S>>>, String> {
+    match seat {
+        Seat::Human => Ok(Box::new(HumanPlayer::new())),
+        Seat::Aided => Ok(Box::new(AiAidedPlayer::new())),
+        Seat::Engine(engine) => {
+            let simulations = match engine {
+                EngineSpec::MonteCarlo { .. } => options.simulations.unwrap_or(ai_player::MONTE_CARLO_SIMULATIONS),
+                _ => options.simulations.unwrap_or(SIMULATIONS),
+            };
+            let time_limit = options.time.unwrap_or(ai_player::DEFAULT_TIME_LIMIT);
+            engine.player(game, simulations, time_limit)
+        }
+    }
+}
+
+/// The player of `seat` for a game on the board of the options, which `check_seats` has
+/// accepted.
+fn seat_player<S: Board>(seat: &Seat, game: &ConnectFour<S>, options: &Options) -> Box<dyn PlayerTrait<Game=ConnectFour<S>>> {
+    player(seat, game, options).expect("checked before the game")
+}
+
+/// Exits if a seat cannot play on the board of the options. Later boards, e. g. of `newgame`
+/// or HTTP requests, are checked when they are set up.
+fn check_seats<S: Board>(options: &Options) {
+    let game = new_game::<S>(options);
+    for seat in &options.seats {
+        if let Err(error) = player(seat, &game, options) {
+            eprintln!("{}", error);
+            process::exit(2);
+        }
+    }
+}
+
+fn new_game<S: Board>(options: &Options) -> ConnectFour<S> {
+    ConnectFour::with_win_length(options.columns, options.rows, options.win_length)
+        .unwrap_or_else(|()| {
+            eprintln!("unsupported board: {}×{} with a win length of {}", options.columns, options.rows, options.win_length);
+            process::exit(2);
+        })
+}
+
+fn run<S: Board>(options: &Options, first: usize) {
+    let game = new_game::<S>(options);
+    // The full screen interface is for humans at a terminal that can show it.
+    let tui = options.tui.unwrap_or_else(|| {
+        options.seats.iter().any(|seat| seat.is_human())
+            && io::stdin().is_terminal()
+            && is_capable_terminal()
+            && options.style.is_none_or(Style::is_ansi)
+    });
+    if tui {
+        run_tui(game, options, first).unwrap_or_else(|error| {
+            eprintln!("terminal error: {}", error);
+            process::exit(1);
+        });
+    }
+    else {
+        run_text(game, options, first, None);
+    }
+}
+
+/// Continues the game saved at `--load`, if any.
+fn load<S: Board>(moves: Moves<ConnectFour<S>>, options: &Options) -> Moves<ConnectFour<S>> {
+    match &options.load {
+        Some(path) => load_moves(path)
+            .and_then(|history| moves.replay(&history).map_err(|err| format!("invalid move: {:?}", err)))
+            .unwrap_or_else(|error| {
+                eprintln!("could not load `{}`: {}", path, error);
+                process::exit(1);
+            }),
+        None => moves,
+    }
+}
+
+fn run_tui<S: Board>(game: ConnectFour<S>, options: &Options, first: usize) -> io::Result<()> {
+    let palette = if options.style == Some(Style::ColourBlind) { Palette::ColourBlind } else { Palette::Standard };
+    let tui = Tui::new(&game, palette)?;
+    let events = event_stream(options);
+    let observe = |player: Box<dyn PlayerTrait<Game=ConnectFour<S>>>| -> Box<dyn PlayerTrait<Game=ConnectFour<S>>> {
+        match &events {
+            Some(events) => Box::new(events.observe(player)),
+            None => player,
+        }
+    };
+    let tui_player = |seat: &Seat| -> Box<dyn PlayerTrait<Game=ConnectFour<S>>> {
+        match seat {
+            Seat::Human => observe(Box::new(tui.human())),
+            Seat::Aided => observe(Box::new(tui.aided())),
+            seat => {
+                // The interface searches with `think`, which the event stream observes inside.
+                let player = seat_player(seat, &game, options);
+                match &events {
+                    Some(events) => Box::new(tui.watch(Box::new(events.observe_search(player)))),
+                    None => Box::new(tui.watch(player)),
+                }
+            }
+        }
+    };
+    let players = vec![tui_player(&options.seats[first]), tui_player(&options.seats[1 - first])];
+    let mut moves = load(game.clone().iter(players), options);
+
+    tui.start(&moves)?;
+    if let Some(events) = &events {
+        events.start(&moves);
+    }
+    while let Some(event) = moves.next() {
+        if let Some(events) = &events {
+            events.write(&event, moves.game());
+        }
+        tui.show(&event)?;
+    }
+    let result = tui.finish()?;
+    println!("{}", result);
+    Ok(())
+}
+
+/// The stream for `--events`: a TCP connection for `tcp:HOST:PORT`, a file or a pipe
+/// otherwise.
+fn event_stream<S: Board>(options: &Options) -> Option<EventStream<S>> {
+    let target = options.events.as_ref()?;
+    let output: io::Result<Box<dyn Write + Send>> = match target.strip_prefix("tcp:") {
+        Some(address) => TcpStream::connect(address).map(|stream| Box::new(stream) as _),
+        None => fs::File::create(target).map(|file| Box::new(file) as _),
+    };
+    match output {
+        Ok(output) => Some(EventStream::new(output)),
+        Err(error) => {
+            eprintln!("could not open `{}`: {}", target, error);
+            process::exit(1);
+        }
+    }
+}
+
+/// What happened in `event`, one line per sentence.
+fn describe<S: State>(event: &Event<ConnectFour<S>>, name: &dyn Fn(Player) -> String) -> Vec<String> {
+    match event {
+        Event::Moved { player, move_, winner, .. } => {
+            let mut lines = vec![format!("Player {} has moved {}", name(*player), move_)];
+            match winner {
+                Winner::Winner(winner) => lines.push(format!("Player {} has won.", name(*winner))),
+                Winner::Draw => lines.push("Draw.".to_string()),
+                Winner::NotFinishedYet => {}
+            }
+            lines
+        }
+        Event::Undone { player, plies: 0, .. } => vec![format!("Player {} has no move to take back.", name(*player))],
+        Event::Undone { player, plies, .. } => vec![format!("Player {} has taken back {} moves", name(*player), plies)],
+        Event::Saved { path, result: Ok(()), .. } => vec![format!("Saved the game to `{}`.", path.display())],
+        Event::Saved { path, result: Err(error), .. } => {
+            vec![format!("Could not save the game to `{}`: {}", path.display(), error)]
+        }
+        Event::Resigned(player) => vec![format!("Player {} has resigned.", name(*player))],
+        Event::DrawOffered { accepted: true, .. } => vec!["The players have agreed to a draw.".to_string()],
+        Event::DrawOffered { player, accepted: false } => {
+            vec![format!("Player {}’s draw offer was declined.", name(*player))]
+        }
+        Event::Quit(player) => vec![format!("Player {} has quit the game.", name(*player))],
+    }
+}
+
+/// Plays on the terminal line by line. The second seat is played by `remote` if given, which
+/// is kept up to date about the game.
+fn run_text<S: Board>(game: ConnectFour<S>, options: &Options, first: usize, remote: Option<RemotePlayer<ConnectFour<S>>>) {
+    let events = event_stream(options);
+    // Remote players are observed like humans, their searches happen elsewhere.
+    let observed = |seat: Option<&Seat>, player| match &events {
+        Some(events) if seat.is_some_and(|seat| !seat.is_human()) => Box::new(events.observe_search(player)),
+        Some(events) => Box::new(events.observe(player)),
+        None => player,
+    };
+    let mut players = vec![
+        observed(Some(&options.seats[0]), seat_player(&options.seats[0], &game, options)),
+        match &remote {
+            Some(remote) => observed(None, Box::new(remote.clone())),
+            None => observed(Some(&options.seats[1]), seat_player(&options.seats[1], &game, options)),
+        },
+    ];
+    players.rotate_left(first);
+    let mut moves = load(game.iter(players), options);
+    if let Some(events) = &events {
+        events.start(&moves);
+    }
+    let style = options.style.unwrap_or_else(Style::detect);
+    let renderer = style.renderer();
+    let board = |state: &S| renderer.board(&render::Board::new(state));
+    let name = |player: Player| renderer.player(player);
+    // Humans need to see the board, spectators want to see every move.
+    let clear_screen = style.is_ansi() && options.seats.iter().any(|seat| seat.is_human());
+
+    if clear_screen {
+        println!("\x1B[2J\x1B[H");
+    }
+    println!("{}", board(moves.game().state()));
+    if let Some(remote) = &remote {
+        remote.show(moves.game());
+    }
+    let mut result = String::new();
+    while let Some(event) = moves.next() {
+        if let Some(events) = &events {
+            events.write(&event, moves.game());
+        }
+        let state = match &event {
+            Event::Moved { state, .. } | Event::Undone { state, .. } => Some(state),
+            _ => None,
+        };
+        if clear_screen && state.is_some() {
+            print!("\x1B[2J\x1B[H");
+        }
+        let mut lines = describe(&event, &name);
+        let mut plain_lines = describe(&event, &|player| player.to_string());
+        if let (Event::Resigned(player), Some(remote)) = (&event, &remote) {
+            if remote.is_disconnected() {
+                lines = vec![format!("Player {} has left the game, which counts as a forfeit.", name(*player))];
+                plain_lines = vec![];
+            }
+        }
+        for line in &lines {
+            println!("{}", line);
+        }
+        if let Some(state) = state {
+            println!("{}", board(state));
+        }
+        if let Some(remote) = &remote {
+            for line in &plain_lines {
+                remote.tell(line);
+            }
+            remote.show(moves.game());
+        }
+        result = plain_lines.last().cloned().unwrap_or_default();
+    }
+    if let Some(remote) = remote {
+        remote.end(&result);
+    }
+}
+
+fn serve<S: Board>(options: &Options, first: usize) {
+    let port = options.port.unwrap_or(remote::DEFAULT_PORT);
+    let listener = TcpListener::bind(("0.0.0.0", port)).unwrap_or_else(|error| {
+        eprintln!("could not listen on port {}: {}", port, error);
+        process::exit(1);
+    });
+    println!("Waiting for an opponent on port {}…", port);
+    let remote = listener.accept()
+        .and_then(|(stream, address)| {
+            println!("{} has joined the game.", address);
+            RemotePlayer::new(stream)
+        })
+        .unwrap_or_else(|error| {
+            eprintln!("could not accept an opponent: {}", error);
+            process::exit(1);
+        });
+    run_text(new_game::<S>(options), options, first, Some(remote));
+}
+
+/// Joins a game served at `address`. The host decides the board, so positions are decoded
+/// into `VecState`s, which support every board.
+fn connect(options: &Options, address: &str) {
+    let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, remote::DEFAULT_PORT) };
+    let stream = TcpStream::connect(&address).unwrap_or_else(|error| {
+        eprintln!("could not connect to {}: {}", address, error);
+        process::exit(1);
+    });
+    let game = new_game::<VecState>(options);
+    let local = seat_player(&options.seats[0], &game, options);
+    let renderer = options.style.unwrap_or_else(Style::detect).renderer();
+    let notify = |notice: Notice<ConnectFour<VecState>>| match notice {
+        Notice::Position(game) => println!("{}", renderer.board(&render::Board::new(game.state()))),
+        Notice::Message(message) => println!("{}", message),
+    };
+    let input = stream.try_clone().map(BufReader::new).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    match remote::play(&*local, input, &mut &stream, notify) {
+        Ok(result) => println!("{}", result),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            println!("The host has left the game, which counts as a forfeit.");
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Reads a game saved with the `save` command: the columns played, separated by whitespace.
+fn load_moves(path: &str) -> Result<Vec<usize>, String> {
+    let moves = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    moves.split_whitespace()
+        .map(|move_| parse_number(move_, "move"))
+        .collect()
+}
+
+fn run_engine<S: Board>(options: &Options) {
+    let seat = &options.seats[1];
+    if seat.is_human() {
+        eprintln!("the engine needs an AI player in the second seat");
+        process::exit(2);
+    }
+    let game = new_game::<S>(options);
+    let stdout = io::stdout();
+    let new_player = |game: &ConnectFour<S>| player(seat, game, options);
+    if let Err(error) = engine::run(game, new_player, BufReader::new(io::stdin()), &mut stdout.lock()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+/// Answers HTTP requests on localhost with the player of the second seat, whose playouts
+/// each request may limit.
+fn run_http<S: Board>(options: &Options) {
+    let engine = match &options.seats[1] {
+        Seat::Engine(engine) => engine.clone(),
+        _ => {
+            eprintln!("the HTTP interface needs an AI player in the second seat");
+            process::exit(2);
+        }
+    };
+    let port = options.port.unwrap_or(http::DEFAULT_PORT);
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|error| {
+        eprintln!("could not listen on port {}: {}", port, error);
+        process::exit(1);
+    });
+    let owned_options = options.clone();
+    let new_player = move |game: &ConnectFour<S>, simulations| {
+        player(&Seat::Engine(engine.clone().with_simulations(simulations)), game, &owned_options)
+    };
+    let server = Arc::new(http::Server::new(new_game::<S>(options), new_player));
+    println!("Listening on http://127.0.0.1:{}", port);
+    if let Err(error) = http::serve(server, listener) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
 
 fn main() {
     env_logger::init();
-    let game = ConnectFour::<BitState>::new(7, 6).unwrap();
-    let human_player = HumanPlayer::new();
-    let ai_player = TreeSearchPlayer::new(&game);
-    let mut players: Vec<Box<PlayerTrait<Game=_>>> = vec![Box::new(human_player), Box::new(ai_player)];
-    players.shuffle(&mut thread_rng());
-
-    println!("\x1B[2J\x1B[H");
-    println!("{}", game.state());
-    for (state, player, move_, winner) in game.iter(players) {
-        print!("\x1B[2J\x1B[H");
-        println!("Player {} has moved {}", player, move_);
-        println!("{}", state);
-        match winner {
-            Winner::Winner(winner) => println!("Player {} has won.", winner),
-            Winner::Draw => println!("Draw."),
-            Winner::NotFinishedYet => {}
-        };
+    let options = parse_arguments().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+
+    let mut rng = match options.seed {
+        Some(seed) => {
+            ai_player::set_seed(seed);
+            StdRng::seed_from_u64(seed)
+        }
+        None => StdRng::from_entropy(),
+    };
+    let first = options.first.unwrap_or_else(|| rng.gen_range(0, 2));
+
+    // Boards that the engine switches to with `newgame`, and boards of HTTP requests, must fit
+    // into the same representation.
+    let bit_state = options.win_length == 4 && BitState::new(options.columns, options.rows).is_ok();
+    match (&options.mode, bit_state) {
+        (Mode::Connect(_), _) => check_seats::<VecState>(&options),
+        (_, true) => check_seats::<BitState>(&options),
+        (_, false) => check_seats::<VecState>(&options),
+    }
+    match (&options.mode, bit_state) {
+        (Mode::Play, true) => run::<BitState>(&options, first),
+        (Mode::Play, false) => run::<VecState>(&options, first),
+        (Mode::Engine, true) => run_engine::<BitState>(&options),
+        (Mode::Engine, false) => run_engine::<VecState>(&options),
+        (Mode::Serve, true) => serve::<BitState>(&options, first),
+        (Mode::Serve, false) => serve::<VecState>(&options, first),
+        (Mode::Http, true) => run_http::<BitState>(&options),
+        (Mode::Http, false) => run_http::<VecState>(&options),
+        (Mode::Connect(address), _) => connect(&options, address),
     }
 }
@@ -0,0 +1,100 @@
+//! A small, versioned binary format for saving search results to disk.
+//!
+//! All integers are stored in little endian byte order, floats as their IEEE 754 bit pattern.
+
+use std::io;
+use std::io::prelude::*;
+
+use super::Player;
+
+
+/// Types that can be written to and read back from a binary stream.
+pub trait Persist: Sized {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self>;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_to(&mut bytes).expect("writing to a `Vec` cannot fail");
+        bytes
+    }
+}
+
+macro_rules! impl_persist_for_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl Persist for $ty {
+                fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+
+                fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+                    let mut bytes = [0; std::mem::size_of::<$ty>()];
+                    reader.read_exact(&mut bytes)?;
+                    Ok(<$ty>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_persist_for_integer!(u8, u16, u32, u64);
+
+/// `usize` is always stored as a `u64` so that files can be shared between platforms.
+impl Persist for usize {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (*self as u64).write_to(writer)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let value = u64::read_from(reader)?;
+        if value > usize::MAX as u64 {
+            return Err(invalid_data("value does not fit into `usize`"));
+        }
+        Ok(value as usize)
+    }
+}
+
+impl Persist for f64 {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.to_bits().write_to(writer)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(f64::from_bits(u64::read_from(reader)?))
+    }
+}
+
+impl Persist for Player {
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.write_to(writer)
+    }
+
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(Player(u8::read_from(reader)?))
+    }
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+
+/// Reasons why a saved file could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// The file does not start with the expected magic bytes.
+    WrongFileType,
+    UnsupportedVersion(u16),
+    /// The file was saved for a different position than the one it was loaded for.
+    PositionMismatch,
+    /// The file is well-formed, but its contents are not consistent with the rules of the game.
+    Inconsistent(&'static str),
+}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> LoadError {
+        LoadError::Io(error)
+    }
+}
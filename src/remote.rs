@@ -0,0 +1,383 @@
+//! Games between two machines over a line based protocol, usually on a TCP connection.
+//!
+//! The host runs the game and plays the seat of its peer with a `RemotePlayer`, the guest
+//! answers with `play`. Positions are written in their `Notation`, moves as by `Display`. The
+//! host sends:
+//!
+//! * `hello mcc4 VERSION` when the guest has connected.
+//! * `act POSITION` when it is the guest’s turn. The guest answers `move MOVE`, `undo`,
+//!   `resign`, `draw?` or `quit`.
+//! * `invalid MESSAGE` if the move was invalid, followed by the next `act`.
+//! * `draw? POSITION` when the host offers a draw. The guest answers `yes` or `no`.
+//! * `position POSITION` and `message TEXT` to keep the guest up to date.
+//! * `error MESSAGE` if an answer was not understood, the host then waits for another answer.
+//! * `end MESSAGE` when the game is over.
+//!
+//! A guest that disconnects forfeits the game, which the host reports as a resignation.
+
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::marker::PhantomData;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::{fallback_move, Action, Notation, PlayerTrait};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 4004;
+
+
+struct Connection {
+    input: Mutex<Box<dyn BufRead + Send>>,
+    output: Mutex<Box<dyn Write + Send>>,
+    disconnected: AtomicBool,
+}
+
+impl Connection {
+    /// Errors are noticed by the next `receive`, so they are ignored here.
+    fn send(&self, line: fmt::Arguments) {
+        let mut output = self.output.lock().unwrap();
+        let _ = writeln!(output, "{}", line).and_then(|_| output.flush());
+    }
+
+    /// The next line without the line break, or `None` once the peer has disconnected.
+    fn receive(&self) -> Option<String> {
+        let mut line = String::new();
+        match self.input.lock().unwrap().read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                self.disconnected.store(true, Ordering::SeqCst);
+                None
+            }
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+}
+
+
+/// A player on the other end of a connection. Clones share the connection, so that the host
+/// can keep one to keep the guest up to date.
+pub struct RemotePlayer<G> {
+    connection: Arc<Connection>,
+    _game: PhantomData<G>,
+}
+
+impl<G> Clone for RemotePlayer<G> {
+    fn clone(&self) -> RemotePlayer<G> {
+        RemotePlayer { connection: self.connection.clone(), _game: PhantomData }
+    }
+}
+
+impl<G: Notation> RemotePlayer<G> {
+    pub fn new(stream: TcpStream) -> io::Result<RemotePlayer<G>> {
+        let input = io::BufReader::new(stream.try_clone()?);
+        Ok(RemotePlayer::with_io(input, stream))
+    }
+
+    /// Greets the peer on `output`.
+    pub fn with_io(input: impl BufRead + Send + 'static, output: impl Write + Send + 'static) -> RemotePlayer<G> {
+        let connection = Connection {
+            input: Mutex::new(Box::new(input)),
+            output: Mutex::new(Box::new(output)),
+            disconnected: AtomicBool::new(false),
+        };
+        connection.send(format_args!("hello mcc4 {}", PROTOCOL_VERSION));
+        RemotePlayer { connection: Arc::new(connection), _game: PhantomData }
+    }
+
+    /// Whether the peer has disconnected, after which it resigns on its turn.
+    pub fn is_disconnected(&self) -> bool {
+        self.connection.disconnected.load(Ordering::SeqCst)
+    }
+
+    pub fn show(&self, game: &G) {
+        self.connection.send(format_args!("position {}", game.notation()));
+    }
+
+    pub fn tell(&self, message: &str) {
+        self.connection.send(format_args!("message {}", message));
+    }
+
+    pub fn end(&self, message: &str) {
+        self.connection.send(format_args!("end {}", message));
+    }
+}
+
+impl<G: Notation> PlayerTrait for RemotePlayer<G> {
+    type Game = G;
+
+    /// Plays `fallback_move` if the peer disconnects. Games are driven with `act`, which resigns
+    /// instead.
+    fn make_move(&self, game: &G) -> G::Move {
+        loop {
+            match self.act(game) {
+                Action::Move(move_) => return move_,
+                _ if self.is_disconnected() => {
+                    let move_ = fallback_move(game);
+                    log::warn!("the peer has disconnected, playing {}", move_);
+                    return move_;
+                }
+                _ => self.connection.send(format_args!("error only moves are possible here")),
+            }
+        }
+    }
+
+    fn act(&self, game: &G) -> Action<G::Move> {
+        self.connection.send(format_args!("act {}", game.notation()));
+        loop {
+            let Some(answer) = self.connection.receive() else {
+                return Action::Resign;
+            };
+            let (command, argument) = answer.split_once(' ').unwrap_or((&answer, ""));
+            match (command, argument.trim()) {
+                ("move", move_) => match move_.parse() {
+                    Ok(move_) => return Action::Move(move_),
+                    Err(_) => self.connection.send(format_args!("error invalid move `{}`", move_)),
+                },
+                ("undo", "") => return Action::Undo,
+                ("resign", "") => return Action::Resign,
+                ("draw?", "") => return Action::OfferDraw,
+                ("quit", "") => return Action::Quit,
+                _ => self.connection.send(format_args!("error unknown answer `{}`", answer)),
+            }
+        }
+    }
+
+    fn accepts_draw(&self, game: &G) -> bool {
+        self.connection.send(format_args!("draw? {}", game.notation()));
+        loop {
+            match self.connection.receive().as_deref() {
+                Some("yes") => return true,
+                Some("no") | None => return false,
+                Some(answer) => self.connection.send(format_args!("error unknown answer `{}`", answer)),
+            }
+        }
+    }
+
+    fn invalid_move(&self, move_: G::InvalidMove) {
+        self.connection.send(format_args!("invalid {:?}", move_));
+    }
+}
+
+
+/// What the host tells the guest besides asking for moves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notice<G> {
+    Position(G),
+    Message(String),
+}
+
+fn invalid_data(message: impl fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Plays the guest’s side of a game with `player` until the host ends the game, and returns
+/// the host’s last message. Fails with `UnexpectedEof` if the host disconnects first.
+pub fn play<G, R, W>(
+    player: &dyn PlayerTrait<Game=G>,
+    mut input: R,
+    output: &mut W,
+    mut notify: impl FnMut(Notice<G>),
+) -> io::Result<String>
+where
+    G: Notation,
+    R: BufRead,
+    W: Write,
+{
+    let mut hello = String::new();
+    input.read_line(&mut hello)?;
+    if hello.trim() != format!("hello mcc4 {}", PROTOCOL_VERSION) {
+        return Err(invalid_data(format_args!("unsupported host `{}`", hello.trim())));
+    }
+
+    // The position and the move of the last `act`, to report invalid moves.
+    let mut last_move: Option<(G, G::Move)> = None;
+    for line in input.lines() {
+        let line = line?;
+        let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+        let position = || G::from_notation(argument).map_err(invalid_data);
+        match command {
+            "act" => {
+                let game = position()?;
+                let action = loop {
+                    match player.act(&game) {
+                        Action::Save(_) => notify(Notice::Message("Only the host can save the game.".to_string())),
+                        action => break action,
+                    }
+                };
+                match action {
+                    Action::Move(move_) => {
+                        writeln!(output, "move {}", move_)?;
+                        last_move = Some((game, move_));
+                    }
+                    Action::Undo => writeln!(output, "undo")?,
+                    Action::Resign => writeln!(output, "resign")?,
+                    Action::OfferDraw => writeln!(output, "draw?")?,
+                    Action::Quit => writeln!(output, "quit")?,
+                    Action::Save(_) => unreachable!(),
+                }
+            }
+            "invalid" => {
+                // The player learns why from the game itself, the host’s message is the fallback.
+                match last_move.take().map(|(mut game, move_)| game.play(move_)) {
+                    Some(Err(invalid_move)) => player.invalid_move(invalid_move),
+                    _ => notify(Notice::Message(format!("Invalid move: {}", argument))),
+                }
+            }
+            "draw?" => {
+                let answer = if player.accepts_draw(&position()?) { "yes" } else { "no" };
+                writeln!(output, "{}", answer)?;
+            }
+            "position" => notify(Notice::Position(position()?)),
+            "message" | "error" => notify(Notice::Message(argument.to_string())),
+            "end" => return Ok(argument.to_string()),
+            _ => return Err(invalid_data(format_args!("unknown command `{}`", line))),
+        }
+        output.flush()?;
+    }
+    Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the host has disconnected"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::{BitState, ConnectFour, Event, Game, HumanPlayer, InvalidMove, Player};
+
+    type Connect4 = ConnectFour<BitState>;
+
+    /// Reads from `input` and keeps a copy of everything that was read.
+    struct Transcript<R> {
+        input: R,
+        text: Arc<Mutex<String>>,
+    }
+
+    impl<R: BufRead> Read for Transcript<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.fill_buf()?.read(buf)?;
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl<R: BufRead> BufRead for Transcript<R> {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            self.input.fill_buf()
+        }
+
+        fn consume(&mut self, amount: usize) {
+            if let Ok(buffer) = self.input.fill_buf() {
+                self.text.lock().unwrap().push_str(&String::from_utf8_lossy(&buffer[..amount]));
+            }
+            self.input.consume(amount);
+        }
+    }
+
+    /// A human player that remembers the invalid moves it was told about.
+    struct Guest {
+        player: HumanPlayer<Connect4>,
+        invalid_moves: Mutex<Vec<String>>,
+    }
+
+    impl PlayerTrait for Guest {
+        type Game = Connect4;
+
+        fn make_move(&self, game: &Connect4) -> usize {
+            self.player.make_move(game)
+        }
+
+        fn act(&self, game: &Connect4) -> Action<usize> {
+            self.player.act(game)
+        }
+
+        fn invalid_move(&self, move_: InvalidMove) {
+            self.invalid_moves.lock().unwrap().push(format!("{:?}", move_));
+            self.player.invalid_move(move_)
+        }
+    }
+
+    /// What the guest saw of a game.
+    struct GuestView {
+        notices: Vec<Notice<Connect4>>,
+        result: io::Result<String>,
+        /// Everything the host sent.
+        received: String,
+        invalid_moves: Vec<String>,
+    }
+
+    /// Hosts a game on localhost against a guest whose human player types `input`, and returns
+    /// the host’s events and what the guest saw.
+    fn host_and_guest(host_moves: &str, input: &'static str) -> (Vec<Event<Connect4>>, GuestView) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let guest = thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            let player = Guest {
+                player: HumanPlayer::with_io(io::Cursor::new(input), io::sink()),
+                invalid_moves: Mutex::new(vec![]),
+            };
+            let received = Arc::new(Mutex::new(String::new()));
+            let transcript = Transcript { input: io::BufReader::new(stream.try_clone().unwrap()), text: received.clone() };
+            let mut notices = vec![];
+            let result = play(&player, transcript, &mut &stream, |notice| notices.push(notice));
+            let received = received.lock().unwrap().clone();
+            GuestView { notices, result, received, invalid_moves: player.invalid_moves.into_inner().unwrap() }
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let remote = RemotePlayer::new(stream).unwrap();
+        let host = HumanPlayer::with_io(io::Cursor::new(host_moves.to_string()), io::sink());
+        let mut moves = Connect4::new(7, 6).unwrap().iter(vec![Box::new(host), Box::new(remote.clone())]);
+        let mut events = vec![];
+        while let Some(event) = moves.next() {
+            remote.show(moves.game());
+            events.push(event);
+        }
+        remote.end("bye");
+        drop(moves);
+        drop(remote);
+        (events, guest.join().unwrap())
+    }
+
+    #[test]
+    fn guests_play_and_see_the_game() {
+        let (events, guest) = host_and_guest("3\n3\n3\n3\n", "4\n4\n4\n");
+        assert_eq!(events.len(), 7);
+        assert!(matches!(events[6], Event::Moved { winner: crate::Winner::Winner(Player(1)), .. }));
+        assert_eq!(guest.notices.len(), 7);
+        match &guest.notices[1] {
+            Notice::Position(game) => assert_eq!(game.notation(), "4 ......./......./......./......./......./...xO.."),
+            notice => panic!("unexpected notice {:?}", notice),
+        }
+        assert_eq!(guest.result.unwrap(), "bye");
+    }
+
+    #[test]
+    fn invalid_moves_are_reported_to_the_guest() {
+        let (events, guest) = host_and_guest("3\n", "9\n4\n");
+        assert!(matches!(events[1], Event::Moved { move_: 4, .. }));
+        assert!(guest.received.lines().any(|line| line == "invalid InvalidColumn(9)"), "{}", guest.received);
+        assert_eq!(guest.invalid_moves, ["InvalidColumn(9)"]);
+        // The guest’s player was told itself, so the host’s message wasn’t needed.
+        assert!(!guest.notices.iter().any(|notice| matches!(notice, Notice::Message(message) if message.starts_with("Invalid move"))));
+    }
+
+    #[test]
+    fn disconnected_guests_forfeit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let guest = thread::spawn(move || drop(TcpStream::connect(address).unwrap()));
+        let (stream, _) = listener.accept().unwrap();
+        guest.join().unwrap();
+
+        let remote = RemotePlayer::new(stream).unwrap();
+        let game = Connect4::new(7, 6).unwrap();
+        assert_eq!(remote.act(&game), Action::Resign);
+        assert!(remote.is_disconnected());
+        assert_eq!(remote.make_move(&game), 0);
+    }
+}
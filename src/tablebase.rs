@@ -0,0 +1,583 @@
+//! Exact game-theoretic values for every position of small boards.
+//!
+//! `Tablebase::build` solves every position of a small board and stores the value and the
+//! distance to the end of the game for each of them. The positions are numbered by a perfect
+//! index (see `Index`), so an entry takes a single byte and no key. They are solved by a
+//! retrograde pass: the positions with `n` stones only depend on those with `n + 1` stones, so
+//! the board is solved layer by layer from the full board back to the empty one.
+//!
+//! `Tablebase::build_into` writes every layer to a file as soon as it is solved, so that only
+//! two layers have to fit into memory. On a single core, 5×4 is built in 3 seconds and 5×5,
+//! with 172 million entries, in about a minute. 6×5 has 9.9 billion entries and takes about an
+//! hour per core. Its largest two layers need 2.7 GB of memory, and it writes a 9.9 GB file,
+//! which has to fit into memory to be loaded for play.
+//!
+//! The file format consists of the magic bytes `MCC4TABL`, the format version, the number of
+//! columns and rows as `u8` and finally the layers from the full board back to the empty board,
+//! one byte per index entry. The upper two bits of an entry hold the value, the lower six bits
+//! the distance, and `0xFF` marks positions without an entry.
+
+use std::io;
+use std::io::prelude::*;
+use std::sync::Arc;
+
+#[cfg(not(feature = "noparallel"))]
+use rayon::prelude::*;
+
+use crate::persist::{LoadError, Persist};
+use crate::{Analysis, BitState, ConnectFour, Game, Oracle, Player, PlayerTrait, SearchConfig, State, TreeSearchPlayer};
+
+const TABLEBASE_MAGIC: &[u8; 8] = b"MCC4TABL";
+const TABLEBASE_FORMAT_VERSION: u16 = 2;
+
+const DISTANCE_MASK: u8 = 0b0011_1111;
+
+
+/// The result of a position with optimal play, for the player to move.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Value {
+    Loss,
+    Draw,
+    Win,
+}
+
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub value: Value,
+    /// The number of plies until the game ends if the winner wins as fast as possible and the
+    /// loser delays the loss as long as possible.
+    pub distance: u8,
+}
+
+impl Entry {
+    /// The entry of the parent position if this entry is reached by a move.
+    fn for_parent(self) -> Entry {
+        let value = match self.value {
+            Value::Loss => Value::Win,
+            Value::Draw => Value::Draw,
+            Value::Win => Value::Loss,
+        };
+        Entry { value, distance: self.distance + 1 }
+    }
+
+    /// Higher is better for the player to move.
+    fn preference(self) -> i32 {
+        match self.value {
+            Value::Win => 1_000 - self.distance as i32,
+            Value::Draw => 0,
+            Value::Loss => -1_000 + self.distance as i32,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        (self.value as u8) << 6 | self.distance
+    }
+
+    fn from_byte(byte: u8) -> Option<Entry> {
+        let value = match byte >> 6 {
+            0 => Value::Loss,
+            1 => Value::Draw,
+            2 => Value::Win,
+            _ => return None,
+        };
+        Some(Entry { value, distance: byte & DISTANCE_MASK })
+    }
+}
+
+
+/// Marks the positions without an entry: those in which a player has already won and the
+/// full board.
+const NOT_COVERED: u8 = 0xFF;
+
+
+/// The stones of a position, independent of the game rules.
+#[derive(Clone, Debug)]
+struct Position {
+    heights: [usize; 12],
+    /// The stones of the first and the second player, column by column from the bottom, with
+    /// an empty bit above every column so that lines don't wrap around.
+    stones: [u64; 2],
+}
+
+impl Position {
+    fn of(state: &BitState) -> Position {
+        let (columns, rows) = state.size();
+        let mut position = Position { heights: [0; 12], stones: [0; 2] };
+        for column in 0..columns {
+            // Rows are numbered from the top.
+            for row in (0..rows).rev() {
+                match state.get(column, row) {
+                    Player(0) => break,
+                    Player(player) => position.stones[player as usize - 1] |= 1 << bit(column, position.heights[column], rows),
+                }
+                position.heights[column] += 1;
+            }
+        }
+        position
+    }
+}
+
+fn bit(column: usize, row: usize, rows: usize) -> usize {
+    column * (rows + 1) + row
+}
+
+/// Whether `stones` contain four in a line, see `Position::stones`.
+fn has_four(stones: u64, rows: usize) -> bool {
+    let height = rows + 1;
+    [1, height - 1, height, height + 1].iter().any(|&shift| {
+        let pairs = stones & (stones >> shift);
+        pairs & (pairs >> (2 * shift)) != 0
+    })
+}
+
+
+/// Numbers the positions of a board without gaps, so that the tablebase needs no keys.
+///
+/// Positions are grouped into layers by their number of stones `n`. The first player always
+/// has `⌈n/2⌉` of them, so a position is given by the heights of its columns and by which of
+/// its stones, counted column by column from the bottom, belong to the first player. The offset
+/// within the layer is the rank of the heights among all heights with the same sum, times the
+/// number of ways to colour `n` stones, plus the rank of the colouring in the combinatorial
+/// number system.
+#[derive(Clone, Debug, PartialEq)]
+struct Index {
+    columns: usize,
+    rows: usize,
+    /// `binomials[n][k]` is `n` choose `k`.
+    binomials: Vec<Vec<u64>>,
+    /// `stackings[k][n]` is the number of ways to stack `n` stones in `k` columns.
+    stackings: Vec<Vec<u64>>,
+}
+
+impl Index {
+    fn new(columns: usize, rows: usize) -> Index {
+        let cells = columns * rows;
+        let mut binomials = vec![vec![0; cells + 2]; cells + 2];
+        for n in 0..cells + 2 {
+            binomials[n][0] = 1;
+            for k in 1..=n {
+                binomials[n][k] = binomials[n - 1][k - 1] + binomials[n - 1][k];
+            }
+        }
+        let mut stackings = vec![vec![0; cells + 1]; columns + 1];
+        stackings[0][0] = 1;
+        for k in 1..=columns {
+            for n in 0..=cells {
+                stackings[k][n] = (0..=rows.min(n)).map(|height| stackings[k - 1][n - height]).sum();
+            }
+        }
+        Index { columns, rows, binomials, stackings }
+    }
+
+    fn cells(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn colourings(&self, stones: usize) -> u64 {
+        self.binomials[stones][stones.div_ceil(2)]
+    }
+
+    fn layer_size(&self, stones: usize) -> usize {
+        (self.stackings[self.columns][stones] * self.colourings(stones)) as usize
+    }
+
+    /// The offset of `position` within its layer of `stones` stones.
+    fn offset(&self, position: &Position, stones: usize) -> usize {
+        let mut heights_rank = 0;
+        let mut left = stones;
+        for column in 0..self.columns {
+            let rest = self.columns - column - 1;
+            heights_rank += (0..position.heights[column]).map(|height| self.stackings[rest][left - height]).sum::<u64>();
+            left -= position.heights[column];
+        }
+        let mut colouring_rank = 0;
+        let mut ordinal = 0;
+        let mut first_player_stones = 0;
+        for column in 0..self.columns {
+            for row in 0..position.heights[column] {
+                if position.stones[0] & 1 << bit(column, row, self.rows) != 0 {
+                    first_player_stones += 1;
+                    colouring_rank += self.binomials[ordinal][first_player_stones];
+                }
+                ordinal += 1;
+            }
+        }
+        (heights_rank * self.colourings(stones) + colouring_rank) as usize
+    }
+
+    /// The position at `offset` within the layer of `stones` stones, the inverse of `offset`.
+    fn position(&self, stones: usize, offset: usize) -> Position {
+        let colourings = self.colourings(stones);
+        let (mut heights_rank, mut colouring_rank) = (offset as u64 / colourings, offset as u64 % colourings);
+        let mut position = Position { heights: [0; 12], stones: [0; 2] };
+        let mut left = stones;
+        for column in 0..self.columns {
+            let rest = self.columns - column - 1;
+            let mut height = 0;
+            while heights_rank >= self.stackings[rest][left - height] {
+                heights_rank -= self.stackings[rest][left - height];
+                height += 1;
+            }
+            position.heights[column] = height;
+            left -= height;
+        }
+        let mut first_player: u64 = 0;
+        let mut ordinal = stones;
+        for first_player_stones in (1..=stones.div_ceil(2)).rev() {
+            ordinal -= 1;
+            while self.binomials[ordinal][first_player_stones] > colouring_rank {
+                ordinal -= 1;
+            }
+            colouring_rank -= self.binomials[ordinal][first_player_stones];
+            first_player |= 1 << ordinal;
+        }
+        let mut ordinal = 0;
+        for column in 0..self.columns {
+            for row in 0..position.heights[column] {
+                let player = if first_player & 1 << ordinal != 0 { 0 } else { 1 };
+                position.stones[player] |= 1 << bit(column, row, self.rows);
+                ordinal += 1;
+            }
+        }
+        position
+    }
+
+    /// The entry of `position` with `stones` stones, given the layer with one stone more.
+    fn solve(&self, position: &Position, stones: usize, next_layer: &[u8]) -> u8 {
+        if stones == self.cells() || position.stones.iter().any(|&stones| has_four(stones, self.rows)) {
+            return NOT_COVERED;
+        }
+        let player = stones % 2;
+        (0..self.columns)
+            .filter(|&column| position.heights[column] < self.rows)
+            .map(|column| {
+                let mut child = position.clone();
+                child.stones[player] |= 1 << bit(column, child.heights[column], self.rows);
+                child.heights[column] += 1;
+                if has_four(child.stones[player], self.rows) {
+                    Entry { value: Value::Win, distance: 1 }
+                }
+                else if stones + 1 == self.cells() {
+                    Entry { value: Value::Draw, distance: 1 }
+                }
+                else {
+                    Entry::from_byte(next_layer[self.offset(&child, stones + 1)])
+                        .expect("positions without four in a line are covered")
+                        .for_parent()
+                }
+            })
+            .max_by_key(|entry| entry.preference())
+            .expect("positions that have not ended have valid moves")
+            .to_byte()
+    }
+
+    #[cfg(not(feature = "noparallel"))]
+    fn solve_layer(&self, stones: usize, next_layer: &[u8]) -> Vec<u8> {
+        (0..self.layer_size(stones)).into_par_iter()
+            .map(|offset| self.solve(&self.position(stones, offset), stones, next_layer))
+            .collect()
+    }
+
+    #[cfg(feature = "noparallel")]
+    fn solve_layer(&self, stones: usize, next_layer: &[u8]) -> Vec<u8> {
+        (0..self.layer_size(stones))
+            .map(|offset| self.solve(&self.position(stones, offset), stones, next_layer))
+            .collect()
+    }
+
+    /// Solves the layers from the full board back to the empty board and hands each of them to
+    /// `finished`. Only the layer being solved and the one before it are kept in memory.
+    fn solve_layers<E>(&self, mut finished: impl FnMut(usize, Vec<u8>) -> Result<Vec<u8>, E>) -> Result<(), E> {
+        let mut next_layer = vec![];
+        for stones in (0..=self.cells()).rev() {
+            let layer = self.solve_layer(stones, &next_layer);
+            log::debug!("solved {} positions with {} stones", layer.len(), stones);
+            next_layer = finished(stones, layer)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tablebase {
+    index: Index,
+    /// The entries of the positions with `n` stones at `layers[n]`.
+    layers: Vec<Vec<u8>>,
+}
+
+impl Tablebase {
+    /// Solves every position of a board with `columns` columns and `rows` rows.
+    pub fn build(columns: usize, rows: usize) -> Tablebase {
+        let index = Self::index(columns, rows);
+        let mut layers = vec![];
+        index.solve_layers(|_, layer| {
+            layers.push(layer.clone());
+            Ok::<_, ()>(layer)
+        }).unwrap();
+        layers.reverse();
+        Tablebase { index, layers }
+    }
+
+    /// Solves the board like `build`, but writes every layer to `writer` as soon as it is solved
+    /// instead of keeping all of them, in the file format of `save`. Returns the entry of the
+    /// empty board and the number of covered positions.
+    pub fn build_into<W: Write>(columns: usize, rows: usize, writer: &mut W) -> io::Result<(Entry, u64)> {
+        let index = Self::index(columns, rows);
+        write_header(writer, columns, rows)?;
+        let mut start = None;
+        let mut covered = 0;
+        index.solve_layers(|stones, layer| {
+            writer.write_all(&layer)?;
+            covered += layer.iter().filter(|&&byte| byte != NOT_COVERED).count() as u64;
+            if stones == 0 {
+                start = Entry::from_byte(layer[0]);
+            }
+            Ok::<_, io::Error>(layer)
+        })?;
+        Ok((start.expect("the empty board is covered"), covered))
+    }
+
+    /// Whether tablebases can be built for boards of this size. Other sizes panic.
+    pub fn supports(columns: usize, rows: usize) -> bool {
+        BitState::new(columns, rows).is_ok() && columns * rows <= DISTANCE_MASK as usize
+    }
+
+    fn index(columns: usize, rows: usize) -> Index {
+        assert!(Self::supports(columns, rows), "unsupported board size for a tablebase");
+        Index::new(columns, rows)
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.index.columns, self.index.rows)
+    }
+
+    /// The number of positions with an entry.
+    pub fn len(&self) -> usize {
+        self.layers.iter().flatten().filter(|&&byte| byte != NOT_COVERED).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The entry of `game`, or `None` if the game has ended or the position is not covered.
+    pub fn entry(&self, game: &ConnectFour<BitState>) -> Option<Entry> {
+        let state = game.state();
+        let stones = state.stones();
+        let first_player_to_move = stones.is_multiple_of(2);
+        if game.has_ended() || game.size() != self.size() || first_player_to_move != (game.current_player() == Player(1)) {
+            return None;
+        }
+        let position = Position::of(state);
+        if position.stones[0].count_ones() as usize != stones.div_ceil(2) {
+            return None;
+        }
+        Entry::from_byte(self.layers[stones][self.index.offset(&position, stones)])
+    }
+
+    /// The entries of all valid moves in `game`, seen by the player to move in `game`.
+    pub fn move_entries(&self, game: &ConnectFour<BitState>) -> Option<Vec<(usize, Entry)>> {
+        self.entry(game)?;
+        game.valid_moves()
+            .into_iter()
+            .map(|column| {
+                let mut child = game.clone();
+                let entry = match child.play(column).unwrap() {
+                    Some(_) => Entry { value: Value::Win, distance: 1 },
+                    None if child.has_ended() => Entry { value: Value::Draw, distance: 1 },
+                    None => self.entry(&child)?.for_parent(),
+                };
+                Some((column, entry))
+            })
+            .collect()
+    }
+}
+
+impl Oracle<ConnectFour<BitState>> for Tablebase {
+    fn best_move(&self, game: &ConnectFour<BitState>) -> Option<usize> {
+        self.move_entries(game)?
+            .into_iter()
+            .max_by_key(|&(column, entry)| (entry.preference(), std::cmp::Reverse(column)))
+            .map(|(column, _)| column)
+    }
+}
+
+
+fn write_header<W: Write>(writer: &mut W, columns: usize, rows: usize) -> io::Result<()> {
+    writer.write_all(TABLEBASE_MAGIC)?;
+    TABLEBASE_FORMAT_VERSION.write_to(writer)?;
+    (columns as u8).write_to(writer)?;
+    (rows as u8).write_to(writer)
+}
+
+impl Tablebase {
+    pub fn save<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_header(writer, self.index.columns, self.index.rows)?;
+        for layer in self.layers.iter().rev() {
+            writer.write_all(layer)?;
+        }
+        Ok(())
+    }
+
+    pub fn load<R: Read>(reader: &mut R) -> Result<Tablebase, LoadError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != TABLEBASE_MAGIC {
+            return Err(LoadError::WrongFileType);
+        }
+        let version = u16::read_from(reader)?;
+        if version != TABLEBASE_FORMAT_VERSION {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+        let columns = u8::read_from(reader)? as usize;
+        let rows = u8::read_from(reader)? as usize;
+        if !Self::supports(columns, rows) {
+            return Err(LoadError::Inconsistent("unsupported board size"));
+        }
+        let index = Index::new(columns, rows);
+        let mut layers = vec![];
+        for stones in (0..=index.cells()).rev() {
+            let size = index.layer_size(stones);
+            // The file may be shorter than it claims, so the layer only grows with the data read.
+            let mut layer = vec![];
+            reader.by_ref().take(size as u64).read_to_end(&mut layer)?;
+            if layer.len() != size {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            if layer.iter().any(|&byte| byte != NOT_COVERED && Entry::from_byte(byte).is_none()) {
+                return Err(LoadError::Inconsistent("invalid entry"));
+            }
+            layers.push(layer);
+        }
+        layers.reverse();
+        Ok(Tablebase { index, layers })
+    }
+}
+
+
+/// Plays perfectly in every position covered by its tablebase, and falls back to a tree search
+/// elsewhere.
+#[derive(Clone)]
+pub struct TablebasePlayer {
+    tablebase: Arc<Tablebase>,
+    fallback: TreeSearchPlayer<ConnectFour<BitState>>,
+}
+
+impl TablebasePlayer {
+    pub fn new(tablebase: Arc<Tablebase>) -> TablebasePlayer {
+        TablebasePlayer {
+            fallback: TreeSearchPlayer::with_config(SearchConfig::default()),
+            tablebase,
+        }
+    }
+}
+
+impl PlayerTrait for TablebasePlayer {
+    type Game = ConnectFour<BitState>;
+
+    fn make_move(&self, game: &ConnectFour<BitState>) -> usize {
+        self.analyse(game).0
+    }
+
+    fn analyse(&self, game: &ConnectFour<BitState>) -> Analysis<usize> {
+        match self.tablebase.best_move(game) {
+            Some(move_) => (move_, vec![]),
+            None => {
+                log::warn!("position is not covered by the tablebase, falling back to tree search");
+                self.fallback.analyse(game)
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::OnceLock;
+
+    fn tablebase() -> &'static Tablebase {
+        static TABLEBASE: OnceLock<Tablebase> = OnceLock::new();
+        TABLEBASE.get_or_init(|| Tablebase::build(4, 4))
+    }
+
+    #[test]
+    fn four_by_four_is_a_draw() {
+        let empty = ConnectFour::<BitState>::from_moves(4, 4, &[]);
+        assert_eq!(tablebase().entry(&empty), Some(Entry { value: Value::Draw, distance: 16 }));
+        // Mirror images have the same entry.
+        assert_eq!(tablebase().entry(&ConnectFour::<BitState>::from_moves(4, 4, &[0, 1])), tablebase().entry(&ConnectFour::<BitState>::from_moves(4, 4, &[3, 2])));
+        // Positions with the wrong player to move are not covered.
+        let mut passed = empty.clone();
+        passed.next_player();
+        assert_eq!(tablebase().entry(&passed), None);
+    }
+
+    #[test]
+    fn perfect_play_wins_as_fast_as_possible() {
+        // The second player has to block.
+        assert_eq!(tablebase().best_move(&ConnectFour::<BitState>::from_moves(4, 4, &[0, 1, 0, 1, 0])), Some(0));
+        let game = ConnectFour::<BitState>::from_moves(4, 4, &[0, 1, 0, 1, 0, 1]);
+        assert_eq!(tablebase().entry(&game), Some(Entry { value: Value::Win, distance: 1 }));
+        assert_eq!(TablebasePlayer::new(Arc::new(tablebase().clone())).make_move(&game), 0);
+    }
+
+    #[test]
+    fn tree_search_consults_the_oracle() {
+        let game = ConnectFour::<BitState>::from_moves(4, 4, &[0, 1, 0, 1, 0, 1]);
+        let config = SearchConfig { simulations: 1, ..SearchConfig::default() };
+        let player = TreeSearchPlayer::with_config(config).with_oracle(Arc::new(tablebase().clone()));
+        assert_eq!(player.analyse(&game), (0, vec![]));
+        // Other board sizes are not covered.
+        let mut game = ConnectFour::<BitState>::new(5, 4).unwrap();
+        game.play(0).unwrap();
+        assert_eq!(tablebase().best_move(&game), None);
+    }
+
+    #[test]
+    fn the_index_numbers_positions_without_gaps() {
+        let index = Index::new(4, 4);
+        for stones in 0..=index.cells() {
+            for offset in 0..index.layer_size(stones) {
+                assert_eq!(index.offset(&index.position(stones, offset), stones), offset);
+            }
+        }
+        let game = ConnectFour::<BitState>::from_moves(4, 4, &[0, 1, 1, 3, 2]);
+        let position = Position::of(game.state());
+        assert_eq!(position.heights[..4], [1, 2, 1, 1]);
+        assert_eq!(position.stones, [1 | 1 << 6 | 1 << 10, 1 << 5 | 1 << 15]);
+
+        // Positions with more than 32 stones number the stones beyond 32 bits.
+        let index = Index::new(7, 6);
+        let mut position = Position { heights: [0; 12], stones: [0; 2] };
+        for column in 0..6 {
+            position.heights[column] = 6;
+            for row in 0..6 {
+                position.stones[(column + row) % 2] |= 1 << bit(column, row, 6);
+            }
+        }
+        let offset = index.offset(&position, 36);
+        let decoded = index.position(36, offset);
+        assert_eq!((decoded.heights, decoded.stones), (position.heights, position.stones));
+    }
+
+    #[test]
+    fn saved_tablebases_can_be_loaded() {
+        let small = Tablebase::build(4, 3);
+        let mut file = vec![];
+        small.save(&mut file).unwrap();
+        assert_eq!(Tablebase::load(&mut &file[..]).unwrap(), small);
+
+        let mut built = vec![];
+        let (start, covered) = Tablebase::build_into(4, 3, &mut built).unwrap();
+        assert_eq!(built, file);
+        assert_eq!(Some(start), small.entry(&ConnectFour::new(4, 3).unwrap()));
+        assert_eq!(covered, small.len() as u64);
+
+        assert!(matches!(Tablebase::load(&mut &file[..file.len() - 1]), Err(LoadError::Io(_))));
+        file[0] = b'X';
+        assert!(matches!(Tablebase::load(&mut &file[..]), Err(LoadError::WrongFileType)));
+    }
+}
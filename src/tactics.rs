@@ -0,0 +1,130 @@
+//! Detection of immediate wins and threats that works for every `Game`.
+//!
+//! The functions in this module try out moves on copies of the game, so they are cheap
+//! enough to run once per move, but not in playouts. Immediate wins are found with
+//! `Game::winning_moves`, which `ConnectFour<BitState>` answers with bitboard operations.
+
+use super::{Analysis, Game};
+
+
+/// The moves with which the player to move wins immediately.
+pub fn winning_moves<G: Game>(game: &G) -> Vec<G::Move> {
+    game.winning_moves()
+}
+
+/// The moves with which the opponent would win immediately if it were their turn.
+pub fn threats<G: Game>(game: &G) -> Vec<G::Move> {
+    let mut opponent_to_move = game.clone();
+    opponent_to_move.next_player();
+    winning_moves(&opponent_to_move)
+}
+
+/// The moves after which the opponent can win immediately.
+pub fn losing_moves<G: Game>(game: &G) -> Vec<G::Move> {
+    game.valid_moves()
+        .into_iter()
+        .filter(|&move_| {
+            let mut game = game.clone();
+            match game.play(move_) {
+                Ok(None) => !game.has_ended() && !winning_moves(&game).is_empty(),
+                _ => false,
+            }
+        })
+        .collect()
+}
+
+/// A move that is forced by the position: an immediate win, or else blocking the opponent’s
+/// immediate win.
+pub fn forced_move<G: Game>(game: &G) -> Option<G::Move> {
+    winning_moves(game).into_iter().next()
+        .or_else(|| {
+            let valid_moves = game.valid_moves();
+            threats(game).into_iter().find(|move_| valid_moves.contains(move_))
+        })
+}
+
+
+/// Guards a search against tactical blunders that unlucky sampling can cause. Forced moves
+/// are played without calling `analyse` at all. Otherwise, if the move chosen by `analyse`
+/// lets the opponent win immediately, it is replaced by the best move according to the search
+/// statistics that does not, or by any such move if the statistics do not contain one.
+pub fn safety_net<G: Game>(game: &G, analyse: impl FnOnce(&G) -> Analysis<G::Move>) -> Analysis<G::Move> {
+    if let Some(move_) = forced_move(game) {
+        return (move_, vec![]);
+    }
+    let (move_, statistics) = analyse(game);
+    let losing_moves = losing_moves(game);
+    if !losing_moves.contains(&move_) {
+        return (move_, statistics);
+    }
+    let safe_move = statistics.iter()
+        .map(|statistics| statistics.move_)
+        .chain(game.valid_moves())
+        .find(|move_| !losing_moves.contains(move_));
+    match safe_move {
+        Some(safe_move) => {
+            log::debug!("move {} lets the opponent win, playing {} instead", move_, safe_move);
+            (safe_move, statistics)
+        }
+        None => (move_, statistics),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+    use crate::{BitState, ConnectFour, MoveStatistics, VecState};
+
+    #[test]
+    fn finds_wins_threats_and_losing_moves() {
+        let game = ConnectFour::<VecState>::from_moves(7, 6, &[0, 1, 0, 1, 0]);
+        assert_eq!(winning_moves(&game), vec![]);
+        assert_eq!(threats(&game), vec![0]);
+        assert_eq!(forced_move(&game), Some(0));
+
+        // The second player has three stones in the second row, so the first player must not
+        // play below either end.
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[1, 1, 2, 2, 6, 3, 6, 3]);
+        assert_eq!(losing_moves(&game), vec![0, 4]);
+        assert_eq!(forced_move(&game), None);
+    }
+
+    #[test]
+    fn bitboard_wins_match_trying_every_move() {
+        let mut rng = rand::thread_rng();
+        for &(columns, rows) in &[(7, 6), (5, 4), (9, 6), (4, 7)] {
+            for _ in 0..50 {
+                let mut bits = ConnectFour::<BitState>::new(columns, rows).unwrap();
+                let mut vec = ConnectFour::<VecState>::new(columns, rows).unwrap();
+                while !bits.has_ended() {
+                    let expected: Vec<_> = bits.valid_moves()
+                        .into_iter()
+                        .filter(|&column| matches!(bits.clone().play(column), Ok(Some(_))))
+                        .collect();
+                    assert_eq!(winning_moves(&bits), expected);
+                    assert_eq!(winning_moves(&vec), expected);
+                    let &column = bits.valid_moves().choose(&mut rng).unwrap();
+                    bits.play(column).unwrap();
+                    vec.play(column).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn safety_net_overrides_blunders() {
+        let statistics = |moves: &[usize]| moves.iter()
+            .map(|&move_| MoveStatistics { move_, visits: 1, value: 0.5 })
+            .collect::<Vec<_>>();
+        // Forced moves are played without searching.
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[0, 1, 0, 1, 0]);
+        assert_eq!(safety_net(&game, |_| unreachable!()), (0, vec![]));
+
+        let game = ConnectFour::<BitState>::from_moves(7, 6, &[1, 1, 2, 2, 6, 3, 6, 3]);
+        assert_eq!(safety_net(&game, |_| (0, statistics(&[0, 4, 5]))), (5, statistics(&[0, 4, 5])));
+        assert_eq!(safety_net(&game, |_| (4, vec![])).0, 1);
+        assert_eq!(safety_net(&game, |_| (3, vec![])).0, 3);
+    }
+}
@@ -0,0 +1,601 @@
+//! A full screen terminal interface for games on `ConnectFour` boards.
+//!
+//! Humans choose columns with the arrow keys, the number keys or the mouse. A side panel shows
+//! the moves so far, the evaluation of the last AI move and how long the AI has been thinking.
+//! The interface follows the events of the usual `Moves` loop: human seats are played by
+//! `TuiPlayer`s, AI players are wrapped in `WatchedPlayer`s that report their search.
+
+use std::cell::Cell;
+use std::io;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event as TerminalEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+
+use crate::connect_four::render::{Board, Highlight, Palette};
+use crate::{
+    fallback_move, Action, Analysis, ConnectFour, Event, Game, Moves, Player, PlayerTrait, SearchConfig, SearchInfo, State, Thought,
+    TreeSearchPlayer, Winner, HINT_SIMULATIONS,
+};
+
+const CELL_WIDTH: u16 = 3;
+const BOARD_LEFT: u16 = 2;
+/// Leaves room for the title and the column marker above the board.
+const BOARD_TOP: u16 = 3;
+/// The side panel moves below the board if it would be narrower than this.
+const PANEL_WIDTH: u16 = 32;
+/// The time a falling disc spends in every row.
+const FALL_DELAY: Duration = Duration::from_millis(20);
+/// How often the thinking time of AI players is updated.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+const HUMAN_KEYS: &str = "←/→ choose  ↵ drop  u undo  ? hint  s save  r resign  d draw?  q quit";
+
+
+/// Everything on the screen, shared by the interface and its players.
+struct Screen {
+    output: io::Stdout,
+    palette: Palette,
+    board: Board,
+    /// A disc on its way down, drawn over the board.
+    falling: Option<(usize, usize, Player)>,
+    to_move: Player,
+    /// The column marked for the human to move.
+    cursor: usize,
+    history: Vec<usize>,
+    /// The search behind the last AI move and the player who made it.
+    evaluation: Option<(Player, SearchInfo<usize>)>,
+    status: String,
+    prompt: String,
+    keys: &'static str,
+}
+
+impl Screen {
+    fn name(&self, Player(player): Player) -> &'static str {
+        match (self.palette, player) {
+            (_, 1) => "Blue",
+            (Palette::Standard, 2) => "Red",
+            (Palette::ColourBlind, 2) => "Orange",
+            _ => "Nobody",
+        }
+    }
+
+    fn colour(&self, Player(player): Player) -> Color {
+        match (self.palette, player) {
+            (Palette::Standard, 1) => Color::Blue,
+            (Palette::Standard, 2) => Color::Red,
+            (Palette::ColourBlind, 1) => Color::AnsiValue(25),
+            (Palette::ColourBlind, 2) => Color::AnsiValue(208),
+            _ => Color::Reset,
+        }
+    }
+
+    /// The column under the terminal cell at `x` and `y`, if any.
+    fn column_at(&self, x: u16, y: u16) -> Option<usize> {
+        let (columns, rows) = self.board.size();
+        let on_board = (BOARD_TOP - 1..=BOARD_TOP + rows as u16 + 2).contains(&y);
+        let column = (x.checked_sub(BOARD_LEFT + 1)? / CELL_WIDTH) as usize;
+        (on_board && column < columns).then_some(column)
+    }
+
+    fn panel(&self, height: usize) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some((player, info)) = &self.evaluation {
+            lines.push(format!("{}’s last search", self.name(*player)));
+            lines.push(format!("  {} visits, depth {}", info.visits, info.depth));
+            if let Some(value) = info.value {
+                lines.push(format!("  expects {:.0} % of a win", 100. * value));
+            }
+            let variation: Vec<_> = info.principal_variation.iter().map(usize::to_string).collect();
+            lines.push(format!("  line {}", variation.join(" ")));
+            lines.push(String::new());
+        }
+        lines.push(self.status.clone());
+        lines.push(self.prompt.clone());
+        if !self.keys.is_empty() {
+            lines.push(String::new());
+            lines.extend(self.keys.split("  ").map(str::to_string));
+        }
+
+        // The moves take up the remaining space, the latest moves are the most interesting.
+        let pairs: Vec<_> = self.history.chunks(2)
+            .enumerate()
+            .map(|(number, pair)| {
+                let pair: Vec<_> = pair.iter().map(usize::to_string).collect();
+                format!("{:>3}. {}", number + 1, pair.join("  "))
+            })
+            .collect();
+        let room = height.saturating_sub(lines.len() + 2);
+        let mut panel = vec!["Moves".to_string()];
+        panel.extend(pairs[pairs.len().saturating_sub(room)..].iter().cloned());
+        panel.push(String::new());
+        panel.extend(lines);
+        panel
+    }
+
+    fn draw(&mut self) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let (columns, rows) = self.board.size();
+        let board_width = 2 + CELL_WIDTH * columns as u16;
+        let board_height = rows as u16 + 3;
+
+        queue!(self.output, terminal::BeginSynchronizedUpdate, terminal::Clear(ClearType::All))?;
+        if width < BOARD_LEFT + board_width || height < BOARD_TOP + board_height {
+            queue!(self.output, cursor::MoveTo(0, 0), Print("Please enlarge the terminal."))?;
+            execute!(self.output, terminal::EndSynchronizedUpdate)?;
+            return Ok(());
+        }
+
+        queue!(self.output, cursor::MoveTo(BOARD_LEFT, 0), Print("Connect Four"))?;
+        let marker_x = BOARD_LEFT + 1 + CELL_WIDTH * self.cursor as u16;
+        let marker_colour = self.colour(self.to_move);
+        queue!(
+            self.output,
+            cursor::MoveTo(marker_x, BOARD_TOP - 1),
+            SetForegroundColor(marker_colour),
+            Print(" ▼ "),
+            ResetColor,
+        )?;
+
+        let horizontal = "─".repeat(columns * CELL_WIDTH as usize);
+        queue!(self.output, cursor::MoveTo(BOARD_LEFT, BOARD_TOP), Print(format!("┌{}┐", horizontal)))?;
+        for row in 0..rows {
+            queue!(self.output, cursor::MoveTo(BOARD_LEFT, BOARD_TOP + 1 + row as u16), Print("│"))?;
+            for column in 0..columns {
+                let (player, highlight) = match self.falling {
+                    Some((falling_column, falling_row, player)) if (falling_column, falling_row) == (column, row) => {
+                        (player, Highlight::None)
+                    }
+                    _ => self.board.get(column, row),
+                };
+                self.draw_cell(player, highlight)?;
+            }
+            queue!(self.output, Print("│"))?;
+        }
+        let numbers: String = (0..columns).map(|column| format!("{:^3}", column)).collect();
+        queue!(
+            self.output,
+            cursor::MoveTo(BOARD_LEFT, BOARD_TOP + 1 + rows as u16),
+            Print(format!("└{}┘", horizontal)),
+            cursor::MoveTo(BOARD_LEFT + 1, BOARD_TOP + 2 + rows as u16),
+            Print(numbers),
+        )?;
+
+        let (panel_x, panel_y) = if width >= BOARD_LEFT + board_width + 2 + PANEL_WIDTH {
+            (BOARD_LEFT + board_width + 2, BOARD_TOP)
+        }
+        else {
+            (BOARD_LEFT, BOARD_TOP + board_height + 1)
+        };
+        let panel_height = height.saturating_sub(panel_y) as usize;
+        for (line, text) in self.panel(panel_height).iter().take(panel_height).enumerate() {
+            let text: String = text.chars().take(width.saturating_sub(panel_x) as usize).collect();
+            queue!(self.output, cursor::MoveTo(panel_x, panel_y + line as u16), Print(text))?;
+        }
+
+        execute!(self.output, terminal::EndSynchronizedUpdate)
+    }
+
+    fn draw_cell(&mut self, player: Player, highlight: Highlight) -> io::Result<()> {
+        if player == Player(0) {
+            return queue!(self.output, Print(" · "));
+        }
+        let text = match highlight {
+            Highlight::LastMove => "(●)",
+            _ => " ● ",
+        };
+        let colour = self.colour(player);
+        queue!(self.output, SetForegroundColor(colour))?;
+        if highlight == Highlight::WinningLine {
+            queue!(self.output, SetAttribute(Attribute::Reverse))?;
+        }
+        queue!(self.output, Print(text), SetAttribute(Attribute::Reset), ResetColor)
+    }
+
+    /// Waits for the next key press or mouse event, redrawing the screen when the terminal
+    /// is resized.
+    fn next_event(&mut self) -> io::Result<TerminalEvent> {
+        loop {
+            self.draw()?;
+            match event::read()? {
+                TerminalEvent::Key(KeyEvent { kind: KeyEventKind::Release, .. }) | TerminalEvent::Resize(..) => {}
+                event => return Ok(event),
+            }
+        }
+    }
+
+    /// Asks for a line of text, `None` if the question is cancelled with escape.
+    fn read_line(&mut self, question: &str) -> io::Result<Option<String>> {
+        let mut answer = String::new();
+        loop {
+            self.prompt = format!("{} {}▏", question, answer);
+            if let TerminalEvent::Key(key) = self.next_event()? {
+                match key.code {
+                    KeyCode::Char(character) if !is_interrupt(&key) => answer.push(character),
+                    KeyCode::Backspace => { answer.pop(); }
+                    KeyCode::Enter if !answer.is_empty() => return Ok(Some(answer)),
+                    KeyCode::Esc => return Ok(None),
+                    _ if is_interrupt(&key) => return Ok(None),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn is_interrupt(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+
+/// The terminal in full screen mode, restored when the `Tui` is dropped.
+pub struct Tui<S: State> {
+    screen: Arc<Mutex<Screen>>,
+    _state: PhantomData<S>,
+}
+
+impl<S: State> Tui<S> {
+    pub fn new(game: &ConnectFour<S>, palette: Palette) -> io::Result<Tui<S>> {
+        let mut output = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(output, terminal::EnterAlternateScreen, event::EnableMouseCapture, cursor::Hide)?;
+        let screen = Screen {
+            output,
+            palette,
+            board: Board::new(game.state()),
+            falling: None,
+            to_move: game.current_player(),
+            cursor: game.size().0 / 2,
+            history: vec![],
+            evaluation: None,
+            status: String::new(),
+            prompt: String::new(),
+            keys: "",
+        };
+        Ok(Tui { screen: Arc::new(Mutex::new(screen)), _state: PhantomData })
+    }
+
+    /// A human player who uses this interface.
+    pub fn human(&self) -> TuiPlayer<S> {
+        TuiPlayer { screen: self.screen.clone(), aided: false, _state: PhantomData }
+    }
+
+    /// A human player who is shown the move the AI would choose.
+    pub fn aided(&self) -> TuiPlayer<S> {
+        TuiPlayer { screen: self.screen.clone(), aided: true, _state: PhantomData }
+    }
+
+    /// Shows the thinking time and the result of the searches of `player`.
+    pub fn watch(&self, player: Box<dyn PlayerTrait<Game=ConnectFour<S>>>) -> WatchedPlayer<S> {
+        WatchedPlayer { player, screen: self.screen.clone() }
+    }
+
+    /// Shows the position of `moves` before the first event.
+    pub fn start(&self, moves: &Moves<ConnectFour<S>>) -> io::Result<()> {
+        let mut screen = self.screen.lock().unwrap();
+        screen.board = Board::new(moves.game().state());
+        screen.to_move = moves.game().current_player();
+        screen.history = moves.history().to_vec();
+        screen.draw()
+    }
+
+    pub fn show(&self, event: &Event<ConnectFour<S>>) -> io::Result<()> {
+        let mut screen = self.screen.lock().unwrap();
+        screen.status = match event {
+            Event::Moved { state, player, move_, winner } => {
+                let (column, row) = state.last_move();
+                for falling_row in 0..row {
+                    screen.falling = Some((column, falling_row, *player));
+                    screen.draw()?;
+                    thread::sleep(FALL_DELAY);
+                }
+                screen.falling = None;
+                screen.board = Board::new(state);
+                screen.history.push(*move_);
+                screen.to_move = Player(3 - player.0);
+                match winner {
+                    Winner::Winner(winner) => format!("{} has won.", screen.name(*winner)),
+                    Winner::Draw => "Draw.".to_string(),
+                    Winner::NotFinishedYet => format!("{} has moved {}.", screen.name(*player), move_),
+                }
+            }
+            Event::Undone { state, player, plies } => {
+                screen.board = Board::new(state);
+                screen.evaluation = None;
+                let moves = screen.history.len() - plies;
+                screen.history.truncate(moves);
+                format!("{} has taken back {} moves.", screen.name(*player), plies)
+            }
+            Event::Saved { path, result: Ok(()), .. } => format!("Saved the game to `{}`.", path.display()),
+            Event::Saved { path, result: Err(error), .. } => {
+                format!("Could not save the game to `{}`: {}", path.display(), error)
+            }
+            Event::Resigned(player) => format!("{} has resigned.", screen.name(*player)),
+            Event::DrawOffered { accepted: true, .. } => "The players have agreed to a draw.".to_string(),
+            Event::DrawOffered { player, accepted: false } => {
+                format!("{}’s draw offer was declined.", screen.name(*player))
+            }
+            Event::Quit(player) => format!("{} has quit the game.", screen.name(*player)),
+        };
+        screen.draw()
+    }
+
+    /// Waits for a key press after the game and returns the last message.
+    pub fn finish(self) -> io::Result<String> {
+        let mut screen = self.screen.lock().unwrap();
+        screen.prompt = "Press any key to leave.".to_string();
+        screen.keys = "";
+        while !matches!(screen.next_event()?, TerminalEvent::Key(_)) {}
+        Ok(screen.status.clone())
+    }
+}
+
+impl<S: State> Drop for Tui<S> {
+    fn drop(&mut self) {
+        // Restoring the terminal has to happen even after a panic, which may have poisoned the
+        // lock.
+        let mut screen = self.screen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = execute!(screen.output, cursor::Show, event::DisableMouseCapture, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+
+/// A human who plays with the keyboard and the mouse, see `Tui::human`.
+pub struct TuiPlayer<S: State> {
+    screen: Arc<Mutex<Screen>>,
+    aided: bool,
+    _state: PhantomData<S>,
+}
+
+impl<S: State> TuiPlayer<S> {
+    fn hint(game: &ConnectFour<S>) -> usize {
+        let config = SearchConfig { simulations: HINT_SIMULATIONS, ..SearchConfig::default() };
+        TreeSearchPlayer::with_config(config).make_move(game)
+    }
+
+    fn choose(&self, screen: &mut Screen, game: &ConnectFour<S>) -> io::Result<Action<usize>> {
+        let columns = game.size().0;
+        screen.to_move = game.current_player();
+        screen.cursor = screen.cursor.min(columns - 1);
+        if self.aided {
+            let suggestion = TuiPlayer::hint(game);
+            screen.status = format!("The AI would choose column {}.", suggestion);
+            screen.cursor = suggestion;
+        }
+        loop {
+            screen.prompt = format!("{}, choose a column.", screen.name(game.current_player()));
+            screen.keys = HUMAN_KEYS;
+            match screen.next_event()? {
+                TerminalEvent::Key(key) => match key.code {
+                    _ if is_interrupt(&key) => return Ok(Action::Quit),
+                    KeyCode::Left => screen.cursor = screen.cursor.saturating_sub(1),
+                    KeyCode::Right => screen.cursor = (screen.cursor + 1).min(columns - 1),
+                    KeyCode::Enter | KeyCode::Down | KeyCode::Char(' ') => return Ok(Action::Move(screen.cursor)),
+                    KeyCode::Char(digit @ '0'..='9') => {
+                        let column = digit.to_digit(10).unwrap_or_else(|| unreachable!()) as usize;
+                        if column < columns {
+                            screen.cursor = column;
+                            return Ok(Action::Move(column));
+                        }
+                    }
+                    KeyCode::Char('u') => return Ok(Action::Undo),
+                    KeyCode::Char('?') => {
+                        screen.status = "Thinking about a hint…".to_string();
+                        screen.draw()?;
+                        let hint = TuiPlayer::hint(game);
+                        screen.status = format!("The AI suggests column {}.", hint);
+                        screen.cursor = hint;
+                    }
+                    KeyCode::Char('s') => {
+                        screen.keys = "";
+                        if let Some(path) = screen.read_line("Save the game to:")? {
+                            return Ok(Action::Save(path.into()));
+                        }
+                    }
+                    KeyCode::Char('r') => return Ok(Action::Resign),
+                    KeyCode::Char('d') => return Ok(Action::OfferDraw),
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(Action::Quit),
+                    _ => {}
+                },
+                TerminalEvent::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        if let Some(column) = screen.column_at(mouse.column, mouse.row) {
+                            screen.cursor = column;
+                            return Ok(Action::Move(column));
+                        }
+                    }
+                    MouseEventKind::Moved => {
+                        if let Some(column) = screen.column_at(mouse.column, mouse.row) {
+                            screen.cursor = column;
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<S: State> PlayerTrait for TuiPlayer<S> {
+    type Game = ConnectFour<S>;
+
+    /// Plays `fallback_move` if the player quits. Games are driven with `act`, which quits
+    /// instead.
+    fn make_move(&self, game: &ConnectFour<S>) -> usize {
+        loop {
+            match self.act(game) {
+                Action::Move(move_) => return move_,
+                Action::Quit => {
+                    let move_ = fallback_move(game);
+                    log::warn!("the player has quit, playing {}", move_);
+                    return move_;
+                }
+                _ => self.screen.lock().unwrap().status = "Only moves are possible here.".to_string(),
+            }
+        }
+    }
+
+    /// Quits if the terminal fails.
+    fn act(&self, game: &ConnectFour<S>) -> Action<usize> {
+        let mut screen = self.screen.lock().unwrap();
+        let action = self.choose(&mut screen, game).unwrap_or(Action::Quit);
+        screen.prompt.clear();
+        screen.keys = "";
+        action
+    }
+
+    fn accepts_draw(&self, game: &ConnectFour<S>) -> bool {
+        let mut screen = self.screen.lock().unwrap();
+        let offerer = screen.name(game.current_player());
+        screen.prompt = format!("{} offers a draw. Do you accept? [y/n]", offerer);
+        let accepted = loop {
+            match screen.next_event() {
+                Ok(TerminalEvent::Key(key)) => match key.code {
+                    KeyCode::Char('y') => break true,
+                    KeyCode::Char('n') | KeyCode::Esc => break false,
+                    _ if is_interrupt(&key) => break false,
+                    _ => {}
+                },
+                Ok(_) => {}
+                Err(_) => break false,
+            }
+        };
+        screen.prompt.clear();
+        accepted
+    }
+
+    fn invalid_move(&self, move_: <Self::Game as Game>::InvalidMove) {
+        self.screen.lock().unwrap().status = format!("Invalid move: {:?}", move_);
+    }
+}
+
+
+/// An AI player whose searches are shown in the side panel, see `Tui::watch`. Pressing `q`
+/// while it thinks ends the game.
+pub struct WatchedPlayer<S: State> {
+    player: Box<dyn PlayerTrait<Game=ConnectFour<S>>>,
+    screen: Arc<Mutex<Screen>>,
+}
+
+impl<S: State> WatchedPlayer<S> {
+    /// Searches like `think` and whether the user asked to quit meanwhile.
+    fn watch(&self, game: &ConnectFour<S>) -> (Thought<usize>, bool) {
+        let start = Instant::now();
+        let last_update = Cell::new(start);
+        let quit = Cell::new(false);
+        let thinking = |screen: &mut Screen| {
+            screen.status = format!("{} is thinking… {:.1} s", screen.name(game.current_player()), start.elapsed().as_secs_f64());
+            screen.draw()
+        };
+        {
+            let mut screen = self.screen.lock().unwrap();
+            screen.to_move = game.current_player();
+            let _ = thinking(&mut screen);
+        }
+
+        let stop = || {
+            if last_update.get().elapsed() >= PROGRESS_INTERVAL {
+                let _ = thinking(&mut self.screen.lock().unwrap());
+                last_update.set(Instant::now());
+            }
+            while event::poll(Duration::ZERO).unwrap_or(false) {
+                match event::read() {
+                    Ok(TerminalEvent::Key(key)) if is_interrupt(&key) || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) => {
+                        quit.set(true);
+                    }
+                    Ok(TerminalEvent::Resize(..)) => {
+                        let _ = self.screen.lock().unwrap().draw();
+                    }
+                    _ => {}
+                }
+            }
+            quit.get()
+        };
+        let thought = self.player.think(game, &stop);
+        (thought, quit.get())
+    }
+}
+
+impl<S: State> PlayerTrait for WatchedPlayer<S> {
+    type Game = ConnectFour<S>;
+
+    fn make_move(&self, game: &ConnectFour<S>) -> usize {
+        let (((move_, _), _), _) = self.watch(game);
+        move_
+    }
+
+    fn analyse(&self, game: &ConnectFour<S>) -> Analysis<usize> {
+        self.player.analyse(game)
+    }
+
+    fn think(&self, game: &ConnectFour<S>, stop: &dyn Fn() -> bool) -> Thought<usize> {
+        self.player.think(game, stop)
+    }
+
+    fn act(&self, game: &ConnectFour<S>) -> Action<usize> {
+        let (((move_, _), info), quit) = self.watch(game);
+        if quit {
+            return Action::Quit;
+        }
+        let mut screen = self.screen.lock().unwrap();
+        screen.evaluation = Some((game.current_player(), info));
+        Action::Move(move_)
+    }
+
+    fn accepts_draw(&self, game: &ConnectFour<S>) -> bool {
+        self.player.accepts_draw(game)
+    }
+
+    fn invalid_move(&self, move_: <Self::Game as Game>::InvalidMove) {
+        self.player.invalid_move(move_)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BitState;
+
+    fn screen(moves: &[usize]) -> Screen {
+        let game = ConnectFour::<BitState>::from_moves(7, 6, moves);
+        Screen {
+            output: io::stdout(),
+            palette: Palette::Standard,
+            board: Board::new(game.state()),
+            falling: None,
+            to_move: game.current_player(),
+            cursor: 3,
+            history: moves.to_vec(),
+            evaluation: None,
+            status: "Red has moved 4.".to_string(),
+            prompt: String::new(),
+            keys: "",
+        }
+    }
+
+    #[test]
+    fn clicks_select_the_column_below_the_pointer() {
+        let screen = screen(&[]);
+        assert_eq!(screen.column_at(BOARD_LEFT + 1, BOARD_TOP + 1), Some(0));
+        assert_eq!(screen.column_at(BOARD_LEFT + 3, BOARD_TOP - 1), Some(0));
+        assert_eq!(screen.column_at(BOARD_LEFT + 4, BOARD_TOP + 6), Some(1));
+        assert_eq!(screen.column_at(BOARD_LEFT + 1 + 3 * 7, BOARD_TOP + 1), None);
+        assert_eq!(screen.column_at(BOARD_LEFT, BOARD_TOP + 1), None);
+        assert_eq!(screen.column_at(BOARD_LEFT + 1, 0), None);
+    }
+
+    #[test]
+    fn the_panel_shows_the_latest_moves() {
+        let screen = screen(&[3, 3, 4, 4, 5]);
+        assert_eq!(screen.panel(20)[..4], ["Moves", "  1. 3  3", "  2. 4  4", "  3. 5"]);
+        assert_eq!(screen.panel(5), ["Moves", "  3. 5", "", "Red has moved 4.", ""]);
+    }
+}
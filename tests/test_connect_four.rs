@@ -0,0 +1,42 @@
+use mcc4::*;
+
+
+/// `valid_moves_fast()` reuses the caller's buffer, so moves left over from an earlier
+/// position must not leak into the result.
+#[test]
+fn test_valid_moves_fast_replaces_the_buffer_contents() {
+    let mut game = ConnectFour::<VecState>::new(7, 6).unwrap();
+    for _ in 0..6 {
+        game.play(3).unwrap();
+    }
+    let mut valid_moves = vec![3, 3, 3];
+    game.valid_moves_fast(&mut valid_moves);
+    assert_eq!(valid_moves, vec![0, 1, 2, 4, 5, 6]);
+}
+
+
+/// `BitState::last_move()` has to report the cell the last stone landed in, like
+/// `VecState` does, and must not panic before the first move.
+#[test]
+fn test_bitstate_last_move_matches_vecstate() {
+    let mut bits = ConnectFour::<BitState>::new(7, 6).unwrap();
+    let mut vec = ConnectFour::<VecState>::new(7, 6).unwrap();
+    assert_eq!(bits.state().last_move(), (0, 0));
+    for &move_ in &[3, 3, 2, 4, 3, 0] {
+        bits.play(move_).unwrap();
+        vec.play(move_).unwrap();
+        assert_eq!(bits.state().last_move(), vec.state().last_move());
+        let (column, row) = bits.state().last_move();
+        assert_eq!(bits.state().get(column, row), vec.state().get(column, row));
+    }
+}
+
+
+#[test]
+fn test_boards_have_a_maximum_size() {
+    assert!(ConnectFour::<VecState>::with_win_length(MAX_BOARD_SIDE, MAX_BOARD_SIDE, 4).is_ok());
+    for &(columns, rows) in &[(MAX_BOARD_SIDE + 1, 6), (7, MAX_BOARD_SIDE + 1), (0, 6), (7, 0), (usize::MAX, 2), (1 << 32, 1 << 32)] {
+        assert!(ConnectFour::<VecState>::with_win_length(columns, rows, 4).is_err(), "{}x{}", columns, rows);
+    }
+    assert!(ConnectFour::<BitState>::new(1, usize::MAX).is_err());
+}
@@ -0,0 +1,59 @@
+use std::io;
+
+use mcc4::*;
+use mcc4::events::EventStream;
+use mcc4::json::Json;
+
+mod common;
+
+use common::SharedOutput;
+
+type Connect4 = ConnectFour<BitState>;
+
+
+fn play(first: Box<dyn PlayerTrait<Game=Connect4>>, second: Box<dyn PlayerTrait<Game=Connect4>>, search: bool) -> Vec<Json> {
+    let output = SharedOutput::default();
+    let events = EventStream::new(output.clone());
+    let observe = |player| if search { events.observe_search(player) } else { events.observe(player) };
+    let players: Vec<Box<dyn PlayerTrait<Game=Connect4>>> = vec![Box::new(events.observe(first)), Box::new(observe(second))];
+    let mut moves = Connect4::new(7, 6).unwrap().iter(players);
+    events.start(&moves);
+    while let Some(event) = moves.next() {
+        events.write(&event, moves.game());
+    }
+    output.text().lines().map(|line| Json::parse(line).unwrap()).collect()
+}
+
+fn field<'a>(line: &'a Json, name: &str) -> &'a Json {
+    line.get(name).unwrap_or_else(|| panic!("no `{}` in {}", name, line))
+}
+
+
+#[test]
+fn test_games_are_written_line_by_line() {
+    let human = |input: &'static str| -> Box<dyn PlayerTrait<Game=Connect4>> {
+        Box::new(HumanPlayer::with_io(io::Cursor::new(input), io::sink()))
+    };
+    let lines = play(human("0\n9\n0\n0\n0\n"), human("1\n1\nresign\n"), false);
+    let events: Vec<_> = lines.iter().map(|line| field(line, "event").as_str().unwrap()).collect();
+    assert_eq!(events, ["start", "move", "move", "invalid_move", "move", "move", "move", "end"]);
+    assert_eq!(field(&lines[0], "position").as_str(), Some("4 ......./......./......./......./......./......."));
+    assert_eq!(field(&lines[3], "move"), &Json::Number(9.));
+    assert!(field(&lines[4], "thinking_time").as_f64().is_some());
+    assert_eq!(lines[4].get("value"), None);
+    assert_eq!(field(&lines[7], "reason").as_str(), Some("resignation"));
+    assert_eq!(field(&lines[7], "winner"), &Json::Number(1.));
+    assert_eq!(field(&lines[7], "player"), &Json::Number(2.));
+}
+
+#[test]
+fn test_searches_are_reported_with_their_evaluation() {
+    let config = SearchConfig { simulations: 200, ..SearchConfig::default() };
+    let human = Box::new(HumanPlayer::with_io(io::Cursor::new("3\nquit\n"), io::sink()));
+    let lines = play(human, Box::new(TreeSearchPlayer::with_config(config)), true);
+    assert_eq!(lines.len(), 4);
+    assert!(field(&lines[2], "visits").as_u64().is_some());
+    assert_eq!(field(&lines[2], "pv").as_array().unwrap()[0], *field(&lines[2], "move"));
+    assert_eq!(field(&lines[3], "reason").as_str(), Some("quit"));
+    assert_eq!(field(&lines[3], "winner"), &Json::Null);
+}
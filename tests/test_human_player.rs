@@ -0,0 +1,68 @@
+use std::io;
+
+use mcc4::*;
+
+mod common;
+
+use common::SharedOutput;
+
+
+fn human(input: &str, output: &SharedOutput) -> Box<dyn PlayerTrait<Game = ConnectFour<BitState>>> {
+    Box::new(HumanPlayer::with_io(io::Cursor::new(input.to_string()), output.clone()))
+}
+
+
+#[test]
+fn test_scripted_humans_play_a_whole_game() {
+    let output = SharedOutput::default();
+    let game = ConnectFour::<BitState>::new(7, 6).unwrap();
+    let players = vec![human("0\n0\n0\n0\n", &output), human("1\nfoo\n1\n7\n1\n", &output)];
+    let events: Vec<_> = game.iter(players).collect();
+    match events.last() {
+        Some(Event::Moved { player, winner: Winner::Winner(winner), .. }) => {
+            assert_eq!(*player, Player(1));
+            assert_eq!(*winner, Player(1));
+        }
+        _ => panic!("the first player should have won"),
+    }
+    let output = output.text();
+    assert!(output.contains("Unknown command `foo`"), "{}", output);
+    assert!(output.contains("Invalid input"), "{}", output);
+}
+
+#[test]
+fn test_humans_quit_when_the_input_ends() {
+    let output = SharedOutput::default();
+    let game = ConnectFour::<BitState>::new(7, 6).unwrap();
+    let players = vec![human("3\n", &output), human("draw?\n", &output)];
+    let events: Vec<_> = game.iter(players).collect();
+    assert_eq!(events.len(), 3);
+    assert!(matches!(events[1], Event::DrawOffered { player: Player(2), accepted: false }));
+    assert!(matches!(events[2], Event::Quit(Player(2))));
+}
+
+#[test]
+fn test_games_abort_when_the_input_ends_mid_game() {
+    let output = SharedOutput::default();
+    let game = ConnectFour::<BitState>::new(7, 6).unwrap();
+    let players = vec![human("0\n0\n", &output), human("1\n", &output)];
+    let events: Vec<_> = game.iter(players).collect();
+    assert_eq!(events.len(), 4);
+    assert!(events[..3].iter().all(|event| matches!(event, Event::Moved { winner: Winner::NotFinishedYet, .. })));
+    assert!(matches!(events[3], Event::Quit(Player(2))));
+}
+
+#[test]
+fn test_aided_humans_are_shown_the_playout_statistics() {
+    let output = SharedOutput::default();
+    // Two columns are left and neither player can win any more.
+    let mut game = ConnectFour::<BitState>::new(4, 4).unwrap();
+    for &move_ in &[0, 1, 0, 1, 2, 3, 2, 3, 1, 0, 1, 0, 3, 2] {
+        assert_eq!(game.play(move_).unwrap(), None);
+    }
+    let player = AiAidedPlayer::with_io(io::Cursor::new("2\n"), output.clone());
+    assert_eq!(player.make_move(&game), 2);
+    let output = output.text();
+    assert!(output.starts_with("move  playouts  wins"), "{}", output);
+    assert!(output.contains("The AI would choose column"), "{}", output);
+}
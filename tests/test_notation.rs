@@ -0,0 +1,44 @@
+use mcc4::*;
+
+
+#[test]
+fn test_notation_round_trips() {
+    let mut game = ConnectFour::<BitState>::new(7, 6).unwrap();
+    assert_eq!(game.notation(), "4 ......./......./......./......./......./.......");
+    for &move_ in &[3, 3, 4, 4, 5, 5] {
+        game.play(move_).unwrap();
+    }
+    assert_eq!(game.notation(), "4 ......./......./......./......./...ooO./...xxx.");
+    let decoded = ConnectFour::<VecState>::from_notation(&game.notation()).unwrap();
+    assert_eq!(decoded.notation(), game.notation());
+    assert_eq!(decoded.current_player(), Player(1));
+    assert_eq!(decoded.winning_moves(), vec![2, 6]);
+
+    game.play(6).unwrap();
+    let decoded = ConnectFour::<BitState>::from_notation(&game.notation()).unwrap();
+    assert_eq!(decoded.winner(), Some(Player(1)));
+}
+
+#[test]
+fn test_notation_keeps_the_win_length() {
+    let game = ConnectFour::<VecState>::from_notation("5 ........./.........").unwrap();
+    assert_eq!(game.win_length(), 5);
+    assert!(ConnectFour::<BitState>::from_notation("5 ........./.........").is_err());
+}
+
+#[test]
+fn test_unreachable_positions_are_rejected() {
+    for notation in &[
+        "4 ..../O...",
+        "4 ..../.Xx.",
+        "4 x.../O...",
+        "4 X.../o...",
+        "4 ..../xX..",
+        "4 ..../xxO.",
+        "4 ..../...",
+        "4 .../..y",
+        "....",
+    ] {
+        assert!(ConnectFour::<VecState>::from_notation(notation).is_err(), "{}", notation);
+    }
+}
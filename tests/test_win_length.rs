@@ -0,0 +1,27 @@
+use mcc4::*;
+
+
+#[test]
+fn test_five_in_a_row_needs_five_stones() {
+    let mut game = ConnectFour::<VecState>::with_win_length(9, 6, 5).unwrap();
+    assert_eq!(game.win_length(), 5);
+    for &move_ in &[0, 8, 1, 8, 2, 8, 3, 7] {
+        assert_eq!(game.play(move_).unwrap(), None);
+    }
+    assert_eq!(game.play(4).unwrap(), Some(Player(1)));
+}
+
+#[test]
+fn test_bitboards_only_support_four_in_a_row() {
+    assert!(ConnectFour::<BitState>::with_win_length(7, 6, 4).is_ok());
+    assert!(ConnectFour::<BitState>::with_win_length(7, 6, 5).is_err());
+    assert!(ConnectFour::<VecState>::with_win_length(7, 6, 1).is_err());
+}
+
+#[test]
+fn test_playouts_on_vec_states_only_play_valid_moves() {
+    let game = ConnectFour::<VecState>::with_win_length(5, 4, 5).unwrap();
+    for _ in 0..100 {
+        ai_player::simulate_game(game.clone());
+    }
+}